@@ -0,0 +1,67 @@
+//! Named Extension Bundles
+//!
+//! Built-in lists of extensions that can be excluded together with a single flag,
+//! and presets of extensions to include together.
+
+/// Common binary, compiled, image and archive extensions
+pub const NO_BINARIES: &[&str] = &[
+    // Compiled objects and libraries
+    "o", "obj", "a", "lib", "so", "dylib", "dll", "exe", "bin", "class", "jar",
+    "pyc", "pyo", "wasm", "rlib", "pdb",
+    // Images
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "tif", "tiff", "webp",
+    // Archives
+    "zip", "tar", "gz", "tgz", "bz2", "xz", "7z", "rar", "zst",
+];
+
+/// Names of the available bundles
+pub const BUNDLE_NAMES: &[&str] = &["no-binaries"];
+
+/// Looks up a bundle by name
+///
+/// # Arguments
+/// * `name` - Name of the bundle, e.g. `no-binaries`
+///
+/// # Returns
+/// * `Option<&'static [&'static str]>` - The bundle's extensions, `None` if unknown
+pub fn bundle(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "no-binaries" => Some(NO_BINARIES),
+        _ => None,
+    }
+}
+
+/// Image extensions
+pub const IMAGES: &[&str] = &["jpg", "jpeg", "png", "gif", "bmp", "svg", "webp", "ico", "tif", "tiff"];
+
+/// Source code extensions of common languages
+pub const CODE: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "c", "h", "cpp", "hpp",
+    "cs", "rb", "php", "swift", "sh",
+];
+
+/// Documentation and plain text extensions
+pub const DOCS: &[&str] = &["md", "rst", "adoc", "txt", "pdf"];
+
+/// Configuration file extensions
+pub const CONFIG: &[&str] = &["toml", "yaml", "yml", "json", "ini", "cfg", "conf"];
+
+/// Names of the built-in presets
+pub const PRESET_NAMES: &[&str] = &["code", "config", "docs", "images"];
+
+/// Looks up a built-in preset by name
+///
+/// # Arguments
+/// * `name` - Name of the preset, e.g. `images`
+///
+/// # Returns
+/// * `Option<&'static [&'static str]>` - The preset's extensions, `None` if unknown
+pub fn preset(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "code" => Some(CODE),
+        "config" => Some(CONFIG),
+        "docs" => Some(DOCS),
+        "images" => Some(IMAGES),
+        _ => None,
+    }
+}
@@ -0,0 +1,65 @@
+//! CSV Export of Directory Structures
+//!
+//! Lists the files of a built `Item` tree as CSV rows for spreadsheet analysis.
+//! Folders only contribute to the relative paths and get no row of their own.
+
+use std::path::Path;
+
+use super::folder_strucure::{is_displayed, FolderStructureOptions, Item};
+
+/// Header row of the CSV output
+const CSV_HEADER: &str = "relative_path,name,extension,size_bytes";
+
+/// Renders the displayed files as CSV
+///
+/// The output starts with a `relative_path,name,extension,size_bytes` header,
+/// then has one row per file in the tree's pre-order. Paths use `/` separators
+/// and are relative to the root. The extension column is empty for files
+/// without one, and the size column for files whose size could not be read.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options deciding which items are displayed
+///
+/// # Returns
+/// * `String` - The CSV document, each row ending with a newline
+pub fn to_csv(root: &Item, options: &FolderStructureOptions) -> String {
+    let mut output = format!("{}\n", CSV_HEADER);
+    if is_displayed(root, options) {
+        match root {
+            Item::File(name, _) => write_row(&mut output, name, root),
+            Item::Folder(_, items, ..) => write_rows(&mut output, items, "", options),
+        }
+    }
+    output
+}
+
+/// Appends the rows of the displayed files among items and their descendants
+fn write_rows(output: &mut String, items: &[Item], parent: &str, options: &FolderStructureOptions) {
+    for item in items.iter().filter(|item| is_displayed(item, options)) {
+        let path = format!("{}{}", parent, item.name());
+        match item {
+            Item::File(..) => write_row(output, &path, item),
+            Item::Folder(_, children, ..) => write_rows(output, children, &format!("{}/", path), options),
+        }
+    }
+}
+
+/// Appends the row of a single file
+fn write_row(output: &mut String, path: &str, item: &Item) {
+    let name = item.name();
+    let extension = Path::new(name).extension().map(|ext| ext.to_string_lossy()).unwrap_or_default();
+    let size = item.metadata().size.map(|size| size.to_string()).unwrap_or_default();
+    output.push_str(&format!("{},{},{},{}\n", quote_csv(path), quote_csv(name), quote_csv(&extension), size));
+}
+
+/// Quotes a CSV field when it contains a comma, a double quote or a line break
+///
+/// Quoted fields have their double quotes doubled, as per RFC 4180.
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,233 @@
+//! Structural Comparison of Directory Trees
+//!
+//! Merges two built `Item` trees by relative path into a single tree whose
+//! entries record on which side they exist, and renders it with diff-like
+//! `-`/`+` markers.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::folder_strucure::{is_displayed, FolderStructureOptions, Item};
+
+/// Where an entry of the merged tree exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffSide {
+    /// In both trees
+    Both,
+
+    /// Only in the left tree
+    Left,
+
+    /// Only in the right tree
+    Right,
+}
+
+impl DiffSide {
+    /// Marker printed in front of the entry's line
+    fn marker(self) -> char {
+        match self {
+            DiffSide::Both => ' ',
+            DiffSide::Left => '-',
+            DiffSide::Right => '+',
+        }
+    }
+}
+
+/// An entry of the merged tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffNode {
+    /// Name of the entry
+    pub name: String,
+
+    /// Whether the entry is a folder
+    pub is_folder: bool,
+
+    /// Where the entry exists
+    pub side: DiffSide,
+
+    /// Merged children of a folder, empty for files
+    pub children: Vec<DiffNode>,
+}
+
+/// Number of files on each side of a comparison
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DiffCounts {
+    /// Files present in both trees
+    pub both: usize,
+
+    /// Files only in the left tree
+    pub left: usize,
+
+    /// Files only in the right tree
+    pub right: usize,
+}
+
+/// Merges the displayed children of two folders by name
+///
+/// A file and a folder sharing a name are distinct entries, reported as one
+/// removed and one added. Folders present on both sides are merged recursively,
+/// while a folder present on one side keeps that side for its whole subtree.
+/// Entries are ordered folders first, then by name.
+///
+/// # Arguments
+/// * `left` - Children of the left folder
+/// * `right` - Children of the right folder
+/// * `options` - Configuration options deciding which items are displayed
+///
+/// # Returns
+/// * `Vec<DiffNode>` - The merged entries
+pub fn diff_items(left: &[Item], right: &[Item], options: &FolderStructureOptions) -> Vec<DiffNode> {
+    // Keyed by (is_file, name) so folders come first and a file never merges with a folder
+    let mut merged = BTreeMap::<(bool, &str), (Option<&Item>, Option<&Item>)>::new();
+    for item in left.iter().filter(|item| is_displayed(item, options)) {
+        merged.entry((matches!(item, Item::File(..)), item.name())).or_default().0 = Some(item);
+    }
+    for item in right.iter().filter(|item| is_displayed(item, options)) {
+        merged.entry((matches!(item, Item::File(..)), item.name())).or_default().1 = Some(item);
+    }
+
+    merged.into_iter()
+        .map(|((is_file, name), sides)| {
+            let (side, children) = match sides {
+                (Some(left), Some(right)) => (DiffSide::Both, diff_items(children_of(left), children_of(right), options)),
+                (Some(left), None) => (DiffSide::Left, diff_items(children_of(left), &[], options)),
+                (None, Some(right)) => (DiffSide::Right, diff_items(&[], children_of(right), options)),
+                (None, None) => unreachable!("every merged entry comes from one side"),
+            };
+            // A folder only on one side is on that side all the way down
+            let children = match side {
+                DiffSide::Both => children,
+                _ => children.into_iter().map(|child| with_side(child, side)).collect(),
+            };
+            DiffNode { name: name.to_string(), is_folder: !is_file, side, children }
+        })
+        .collect()
+}
+
+/// Children of a folder item, none for a file
+fn children_of(item: &Item) -> &[Item] {
+    match item {
+        Item::Folder(_, items, ..) => items,
+        Item::File(..) => &[],
+    }
+}
+
+/// Sets the side of a node and all its descendants
+fn with_side(mut node: DiffNode, side: DiffSide) -> DiffNode {
+    node.side = side;
+    node.children = node.children.into_iter().map(|child| with_side(child, side)).collect();
+    node
+}
+
+/// Counts the files of a merged tree by side
+///
+/// # Arguments
+/// * `nodes` - The merged entries
+///
+/// # Returns
+/// * `DiffCounts` - The number of files on each side
+pub fn count_sides(nodes: &[DiffNode]) -> DiffCounts {
+    let mut counts = DiffCounts::default();
+    for node in nodes {
+        if node.is_folder {
+            let child_counts = count_sides(&node.children);
+            counts.both += child_counts.both;
+            counts.left += child_counts.left;
+            counts.right += child_counts.right;
+        } else {
+            match node.side {
+                DiffSide::Both => counts.both += 1,
+                DiffSide::Left => counts.left += 1,
+                DiffSide::Right => counts.right += 1,
+            }
+        }
+    }
+    counts
+}
+
+/// Writes a merged tree, each line starting with its side's marker
+///
+/// Lines start with `-` for entries only in the left tree, `+` for entries
+/// only in the right tree and a space for entries in both, followed by a
+/// space and the usual tree connectors:
+///
+/// ```text
+///   ├── src/
+///   │   ├── main.rs
+/// + │   ├── new.rs
+/// - │   └── old.rs
+///   └── Cargo.toml
+/// ```
+///
+/// # Arguments
+/// * `nodes` - The merged entries
+/// * `out` - The sink to write to
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+pub fn write_diff<W: Write>(nodes: &[DiffNode], out: &mut W) -> io::Result<()> {
+    write_nodes(nodes, "", out)
+}
+
+/// Writes merged entries below the given connector prefix
+fn write_nodes<W: Write>(nodes: &[DiffNode], prefix: &str, out: &mut W) -> io::Result<()> {
+    for (i, node) in nodes.iter().enumerate() {
+        let is_last = i == nodes.len() - 1;
+        let connector = if is_last { "└── " } else { "├── " };
+        let suffix = if node.is_folder { "/" } else { "" };
+        writeln!(out, "{} {}{}{}{}", node.side.marker(), prefix, connector, node.name, suffix)?;
+
+        let child_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+        write_nodes(&node.children, &child_prefix, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::folder_utility::folder_strucure::{FolderStructureOptionsBuilder, ItemMetadata};
+
+    /// Builds a file item
+    fn file(name: &str) -> Item {
+        Item::File(name.to_string(), ItemMetadata::default())
+    }
+
+    /// Builds a folder item holding files
+    fn folder(name: &str, items: Vec<Item>) -> Item {
+        Item::Folder(name.to_string(), items, Some(true), ItemMetadata::default())
+    }
+
+    /// Builds an expected merged entry
+    fn node(name: &str, is_folder: bool, side: DiffSide, children: Vec<DiffNode>) -> DiffNode {
+        DiffNode { name: name.to_string(), is_folder, side, children }
+    }
+
+    #[test]
+    fn diff_items_merges_both_sides_by_name() {
+        let left = [
+            folder("build", vec![file("out.o")]),
+            folder("src", vec![file("main.rs"), file("old.rs")]),
+            file("docs"),
+        ];
+        let right = [
+            folder("docs", vec![file("guide.md")]),
+            folder("src", vec![file("main.rs"), file("new.rs")]),
+        ];
+        let options = FolderStructureOptionsBuilder::default().build().unwrap();
+        let merged = diff_items(&left, &right, &options);
+
+        assert_eq!(merged, [
+            node("build", true, DiffSide::Left, vec![node("out.o", false, DiffSide::Left, vec![])]),
+            // A folder and a file of the same name stay distinct entries
+            node("docs", true, DiffSide::Right, vec![node("guide.md", false, DiffSide::Right, vec![])]),
+            node("src", true, DiffSide::Both, vec![
+                node("main.rs", false, DiffSide::Both, vec![]),
+                node("new.rs", false, DiffSide::Right, vec![]),
+                node("old.rs", false, DiffSide::Left, vec![]),
+            ]),
+            node("docs", false, DiffSide::Left, vec![]),
+        ]);
+        assert_eq!(count_sides(&merged), DiffCounts { both: 1, left: 3, right: 2 });
+    }
+}
@@ -0,0 +1,74 @@
+//! Graphviz DOT Export of Directory Structures
+//!
+//! Renders an `Item` tree as a DOT digraph, one node per entry and one edge
+//! from each folder to each of its children, ready for `dot -Tsvg`.
+
+use super::folder_strucure::{format_annotations, is_displayed, FolderStructureOptions, Item};
+
+/// Renders the folder structure as a Graphviz DOT digraph
+///
+/// Node IDs are the entries' paths relative to the root, so they stay unique
+/// when names repeat across folders. Folders are drawn as boxes and files as
+/// plain text.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options for display
+///
+/// # Returns
+/// * `String` - The DOT document
+pub fn to_dot(root: &Item, options: &FolderStructureOptions) -> String {
+    let mut dot = String::from("digraph tree {\n");
+    dot.push_str("  rankdir=LR;\n");
+    dot.push_str("  node [fontname=\"monospace\"];\n");
+    write_nodes(root, ".", None, options, &mut dot);
+    dot.push_str("}\n");
+    dot
+}
+
+/// Appends the node of an item, the edge from its parent, and its displayed descendants
+///
+/// # Arguments
+/// * `item` - The item to render
+/// * `id` - Path of the item relative to the root, `.` for the root
+/// * `parent` - ID of the parent folder and its total size, `None` for the root
+/// * `options` - Configuration options for display
+/// * `dot` - The document rendered so far
+fn write_nodes(item: &Item, id: &str, parent: Option<(&str, Option<u64>)>, options: &FolderStructureOptions, dot: &mut String) {
+    if !is_displayed(item, options) {
+        return;
+    }
+
+    let annotation = format_annotations(item, parent.and_then(|(_, size)| size), options);
+    let shape = match item {
+        Item::File(..) => "plaintext",
+        Item::Folder(..) => "box",
+    };
+    dot.push_str(&format!(
+        "  \"{}\" [label=\"{}{}\", shape={}];\n",
+        escape_dot(id),
+        escape_dot(item.name()),
+        escape_dot(&annotation),
+        shape
+    ));
+
+    if let Some((parent_id, _)) = parent {
+        dot.push_str(&format!("  \"{}\" -> \"{}\";\n", escape_dot(parent_id), escape_dot(id)));
+    }
+
+    if let Item::Folder(_, items, _, metadata) = item {
+        for child in items {
+            let child_id = if id == "." {
+                child.name().to_string()
+            } else {
+                format!("{}/{}", id, child.name())
+            };
+            write_nodes(child, &child_id, Some((id, metadata.size)), options, dot);
+        }
+    }
+}
+
+/// Escapes a string for use inside a double-quoted DOT ID or label
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,144 @@
+//! Duplicate File Detection
+//!
+//! Finds files with identical contents in a built `Item` tree. Files are first
+//! grouped by size, which is already known from traversal, and only files sharing
+//! a size are read and hashed, so unique sizes cost nothing.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::path::Path;
+
+use super::folder_strucure::{is_displayed, FolderStructureOptions, FsError, Item};
+
+/// Size of the chunks files are streamed through the hasher with
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Files sharing the same contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    /// Size in bytes of each file
+    pub size: u64,
+
+    /// Paths of the files relative to the root, in tree order
+    pub paths: Vec<String>,
+}
+
+/// Result of a duplicate search
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    /// Groups of identical files, largest files first
+    pub groups: Vec<DuplicateGroup>,
+
+    /// Files that could not be read, and were left out of the groups
+    pub unreadable: Vec<FsError>,
+}
+
+/// Groups the displayed files of a structure by content
+///
+/// Symlinks are skipped so a link is not reported as a copy of its target, and
+/// so are empty files, which are all trivially identical. Contents are compared
+/// through a streamed 64-bit hash: distinct files sharing a size and a hash
+/// would be reported together, which is negligible in practice.
+///
+/// # Arguments
+/// * `root_path` - Path the structure was built from, used to open the files
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options deciding which items are displayed
+///
+/// # Returns
+/// * `Duplicates` - The groups of two or more identical files and the files that failed to read
+pub fn find_duplicates(root_path: &Path, root: &Item, options: &FolderStructureOptions) -> Duplicates {
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    if let Item::Folder(_, items, ..) = root {
+        if is_displayed(root, options) {
+            collect_files(items, "", options, &mut by_size);
+        }
+    }
+
+    let mut duplicates = Duplicates::default();
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut by_hash: HashMap<u64, Vec<String>> = HashMap::new();
+        for path in paths {
+            let full_path = root_path.join(&path);
+            match hash_file(&full_path) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(path),
+                Err(e) => duplicates.unreadable.push(FsError::IoError(e, Some(full_path))),
+            }
+        }
+        duplicates.groups.extend(by_hash.into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|paths| DuplicateGroup { size, paths }));
+    }
+
+    duplicates.groups.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.paths.cmp(&b.paths)));
+    duplicates
+}
+
+/// Adds the displayed, non-empty regular files among items and their descendants to the size buckets
+fn collect_files(items: &[Item], parent: &str, options: &FolderStructureOptions, by_size: &mut HashMap<u64, Vec<String>>) {
+    for item in items.iter().filter(|item| is_displayed(item, options)) {
+        let path = format!("{}{}", parent, item.name());
+        match item {
+            Item::File(_, metadata) if metadata.symlink => {}
+            Item::File(_, metadata) => {
+                if let Some(size) = metadata.size.filter(|&size| size > 0) {
+                    by_size.entry(size).or_default().push(path);
+                }
+            }
+            Item::Folder(_, children, ..) => collect_files(children, &format!("{}/", path), options, by_size),
+        }
+    }
+}
+
+/// Hashes the contents of a file, reading it in chunks
+///
+/// # Arguments
+/// * `path` - The file to hash
+///
+/// # Returns
+/// * `io::Result<u64>` - The hash of the contents, or the error opening or reading the file
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(read) => hasher.write(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::folder_utility::folder_strucure::{get_folder_structure, FolderStructureOptionsBuilder};
+
+    #[test]
+    fn find_duplicates_groups_identical_non_empty_files() {
+        let root_path = std::env::temp_dir().join(format!("folder-cli-dupes-{}", std::process::id()));
+        fs::create_dir_all(root_path.join("sub")).unwrap();
+        fs::write(root_path.join("a.txt"), "hello").unwrap();
+        fs::write(root_path.join("sub/b.txt"), "hello").unwrap();
+        fs::write(root_path.join("c.txt"), "world").unwrap();
+        fs::write(root_path.join("empty1"), "").unwrap();
+        fs::write(root_path.join("empty2"), "").unwrap();
+
+        let options = FolderStructureOptionsBuilder::default().build().unwrap();
+        let duplicates = get_folder_structure(&root_path, &options)
+            .map(|root| find_duplicates(&root_path, &root, &options));
+        fs::remove_dir_all(&root_path).unwrap();
+
+        // The same-size file with other contents and the empty files are left out, and the
+        // paths follow the tree order, folders first
+        let duplicates = duplicates.unwrap();
+        assert_eq!(duplicates.groups, [DuplicateGroup { size: 5, paths: vec!["sub/b.txt".to_string(), "a.txt".to_string()] }]);
+        assert!(duplicates.unreadable.is_empty());
+    }
+}
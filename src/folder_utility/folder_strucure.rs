@@ -8,343 +8,4718 @@
 //! - Filter items using regex patterns
 //! - Control visibility of empty folders
 //! - Sort items (folders before files, alphabetically within types)
+//! - Show only recently active directories
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::fmt;
 use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::ops::ControlFlow;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::slice;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicUsize};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 use derive_builder::Builder;
-use regex::Regex;
+use regex::{Regex, RegexSet, RegexSetBuilder};
+
+use super::gitignore::{glob_to_regex, Gitignore};
+use super::magic::detect_extension_mismatch;
+use super::summary::Summary;
+use super::units::{format_relative_time, format_size, format_timestamp};
 
 type FsResult<T> = Result<T, FsError>;
 
-// Custom error type to avoid using std::io::Error
+/// Errors of a traversal, wrapping `std::io::Error` alongside the outcomes that end it without output
 #[derive(Debug)]
 pub enum FsError {
-    IoError,
+    /// A read failed, with the path it was reading when known
+    IoError(io::Error, Option<PathBuf>),
+
+    /// The entry was left out by the filters
     Filtered,
+
+    /// The folder has no displayed entries
+    EmptyFolder,
+
+    /// No entry matches the target path
+    NoMatch,
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsError::IoError(error, Some(path)) => write!(f, "{} at {}", error, path.display()),
+            FsError::IoError(error, None) => write!(f, "{}", error),
+            FsError::Filtered => write!(f, "entry excluded by the filters"),
+            FsError::EmptyFolder => write!(f, "folder has no displayed entries"),
+            FsError::NoMatch => write!(f, "no entry matches the target path"),
+        }
+    }
+}
+
+impl std::error::Error for FsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FsError::IoError(error, _) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl FsError {
+    /// Wraps an IO error with the path being read when it occurred
+    fn at(error: io::Error, path: &Path) -> FsError {
+        FsError::IoError(error, Some(path.to_path_buf()))
+    }
+}
+
+/// What an output written while traversing went through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamSummary {
+    /// Number of files written
+    pub files: usize,
+
+    /// Whether `max_total_entries` stopped the traversal before every entry was visited
+    pub entry_limit_reached: bool,
+}
+
+/// Why an output written while traversing stopped early
+#[derive(Debug)]
+pub enum StreamError {
+    /// The traversal failed
+    Traversal(FsError),
+
+    /// The sink failed, e.g. a closed pipe; the traversal was stopped
+    Write(io::Error),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Traversal(error) => write!(f, "{}", error),
+            StreamError::Write(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Traversal(error) => Some(error),
+            StreamError::Write(error) => Some(error),
+        }
+    }
+}
+
+impl From<FsError> for StreamError {
+    fn from(error: FsError) -> Self {
+        StreamError::Traversal(error)
+    }
+}
+
+/// Metadata captured for an item during traversal
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ItemMetadata {
+    /// Last modification time. For folders, the most recent modification among descendant files
+    pub modified: Option<SystemTime>,
+
+    /// Size in bytes. For folders, the total size of all descendant files
+    pub size: Option<u64>,
+
+    /// Whether the folder was listed without traversing its children
+    pub truncated: bool,
+
+    /// Marker displayed after the name, e.g. `reparse`
+    pub note: Option<String>,
+
+    /// Whether the item is the highlighted path
+    pub highlighted: bool,
+
+    /// Why the file passed the filters, captured when `explain` is set
+    pub explanation: Option<String>,
+
+    /// Whether the entry is a symbolic link
+    pub symlink: bool,
+
+    /// Whether the file has an execute permission bit set (Unix only)
+    pub executable: bool,
+
+    /// Where a symlinked file, or a symlinked folder listed without its contents,
+    /// points, printed as `-> target`
+    pub link: Option<String>,
+
+    /// Inode number of the file, captured with `show_inodes` (Unix only)
+    pub inode: Option<u64>,
+
+    /// Number of hard links to the file, captured with `show_inodes` (Unix only)
+    pub hard_links: Option<u64>,
+
+    /// Permission bits of the entry, including setuid, setgid and sticky, captured
+    /// with `show_perms` (Unix only)
+    pub mode: Option<u32>,
+
+    /// Whether `max_total_entries` stopped the traversal before every entry was visited,
+    /// set on the root only
+    pub entry_limit_reached: bool,
+}
+
+/// Why an item passed a filter
+#[derive(Debug, Clone, PartialEq)]
+pub enum InclusionReason {
+    /// No filter of this kind is active
+    NoFilters,
+
+    /// The extension is in the include list
+    IncludedExtension(String),
+
+    /// The extension is not in the exclude list
+    NotExcludedExtension,
+
+    /// No exclude pattern matched the name
+    NoPatternMatched,
+
+    /// The name missed at least one exclude pattern, with `FilterMode::All`
+    NotAllPatternsMatched,
+
+    /// The name matched this include pattern
+    IncludedPattern(String),
+
+    /// The file passed the ownership filters
+    OwnerMatched,
+
+    /// The file size is within the size bounds
+    SizeMatched,
+
+    /// The file name length is within the name length bounds
+    NameLengthMatched,
+
+    /// The file modification time is within the age bounds
+    AgeMatched,
+
+    /// The file is named after its parent folder
+    ModFile,
+
+    /// The file content disagrees with its extension
+    ExtMismatch,
+}
+
+/// Progress of a traversal, reported to the `on_progress` callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// This many entries were scanned so far, reported every `PROGRESS_INTERVAL` entries
+    Scanned(usize),
+
+    /// The traversal ended after scanning this many entries
+    Done(usize),
+}
+
+/// Callback receiving the progress of a traversal
+///
+/// Folders may be traversed in parallel, so the callback can be called from several threads.
+pub type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Callback receiving each entry left out of a structure, with its root-relative path
+///
+/// Folders may be traversed in parallel, so the callback can be called from several threads.
+pub type SkipCallback = Arc<dyn Fn(&str, &ExclusionReason) + Send + Sync>;
+
+/// Why an entry was left out of the structure
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExclusionReason {
+    /// The name starts with a dot and hidden entries are not shown
+    Hidden,
+
+    /// The extension is in the exclude list
+    ExcludedExtension(String),
+
+    /// The extension is not in the include list
+    NotIncludedExtension,
+
+    /// The name matched this exclude pattern
+    ExcludedPattern(String),
+
+    /// The name matched every exclude pattern, with `FilterMode::All`
+    AllPatternsMatched,
+
+    /// The name matched no include pattern
+    NoIncludedPattern,
+
+    /// The path relative to the root matched this exclude pattern
+    ExcludedPath(String),
+
+    /// A `.gitignore` rule ignores the entry
+    Gitignored,
+
+    /// The globs exclude the path, or none of the include globs matches it
+    Glob,
+
+    /// The file is not named after its parent folder
+    NotModFile,
+
+    /// The file failed the ownership filters
+    Owner,
+
+    /// The file size is outside the size bounds
+    Size,
+
+    /// The file name is shorter or longer than the name length bounds
+    NameLength,
+
+    /// The file modification time is outside the age bounds
+    Age,
+
+    /// The file content agrees with its extension, or its type is unknown
+    NoExtMismatch,
+
+    /// The folder has nothing left to show
     EmptyFolder,
+
+    /// The name is not valid UTF-8, so the name filters cannot be applied
+    InvalidName,
+}
+
+impl fmt::Display for ExclusionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExclusionReason::Hidden => write!(f, "hidden"),
+            ExclusionReason::ExcludedExtension(ext) => write!(f, "extension excluded:{}", ext.trim_start_matches('.')),
+            ExclusionReason::NotIncludedExtension => write!(f, "extension not included"),
+            ExclusionReason::ExcludedPattern(pattern) => write!(f, "pattern matched /{}/", pattern),
+            ExclusionReason::AllPatternsMatched => write!(f, "all exclude patterns matched"),
+            ExclusionReason::NoIncludedPattern => write!(f, "no include pattern matched"),
+            ExclusionReason::ExcludedPath(pattern) => write!(f, "path pattern matched /{}/", pattern),
+            ExclusionReason::Gitignored => write!(f, "gitignored"),
+            ExclusionReason::Glob => write!(f, "glob filtered"),
+            ExclusionReason::NotModFile => write!(f, "not named after parent"),
+            ExclusionReason::Owner => write!(f, "owner filtered"),
+            ExclusionReason::Size => write!(f, "size out of range"),
+            ExclusionReason::NameLength => write!(f, "name length out of range"),
+            ExclusionReason::Age => write!(f, "age out of range"),
+            ExclusionReason::NoExtMismatch => write!(f, "extension matches content"),
+            ExclusionReason::EmptyFolder => write!(f, "empty folder"),
+            ExclusionReason::InvalidName => write!(f, "name is not valid UTF-8"),
+        }
+    }
+}
+
+impl fmt::Display for InclusionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InclusionReason::NoFilters => write!(f, "no filters"),
+            InclusionReason::IncludedExtension(ext) => write!(f, "matched include:{}", ext.trim_start_matches('.')),
+            InclusionReason::NotExcludedExtension => write!(f, "extension not excluded"),
+            InclusionReason::NoPatternMatched => write!(f, "no exclude pattern matched"),
+            InclusionReason::NotAllPatternsMatched => write!(f, "not all exclude patterns matched"),
+            InclusionReason::IncludedPattern(pattern) => write!(f, "matched pattern:{}", pattern),
+            InclusionReason::OwnerMatched => write!(f, "owner matched"),
+            InclusionReason::SizeMatched => write!(f, "size in range"),
+            InclusionReason::NameLengthMatched => write!(f, "name length in range"),
+            InclusionReason::AgeMatched => write!(f, "age in range"),
+            InclusionReason::ModFile => write!(f, "named after parent"),
+            InclusionReason::ExtMismatch => write!(f, "extension mismatch"),
+        }
+    }
 }
 
 /// Represents an item in the file system, either a file or a folder
 #[derive(Debug, PartialEq)]
 pub enum Item {
-    /// A file with its name
-    File(String),
+    /// A file with its name and captured metadata
+    File(String, ItemMetadata),
 
     /// A folder with its name, contained items, and a flag indicating if it contains any files
     /// The bool flag indicates whether this folder contains any terminal files (directly or indirectly)
-    Folder(String, Vec<Item>, Option<bool>)
+    Folder(String, Vec<Item>, Option<bool>, ItemMetadata)
+}
+
+impl Item {
+    /// Returns the name of the item
+    pub fn name(&self) -> &str {
+        match self {
+            Item::File(name, _) | Item::Folder(name, ..) => name,
+        }
+    }
+
+    /// Returns the metadata captured for the item
+    pub fn metadata(&self) -> &ItemMetadata {
+        match self {
+            Item::File(_, metadata) | Item::Folder(.., metadata) => metadata,
+        }
+    }
+
+    /// Returns whether the item is a file or a folder holding a terminal file,
+    /// as computed once the structure is built
+    pub fn has_terminal_file(&self) -> bool {
+        match self {
+            Item::File(..) => true,
+            Item::Folder(_, _, has_terminal, _) => has_terminal.unwrap_or(false),
+        }
+    }
+
+    /// Iterates over the item and all its descendants in pre-order
+    ///
+    /// Each folder is yielded before its children, which keep their sorted
+    /// order. Every node of the structure is yielded, including the empty
+    /// folders the printers hide unless `show_empty_folder` is set.
+    ///
+    /// # Returns
+    /// * `ItemIter` - An iterator of items paired with their depth, 0 for this item
+    pub fn iter(&self) -> ItemIter<'_> {
+        ItemIter { root: Some(self), stack: Vec::new() }
+    }
+}
+
+/// Pre-order iterator over an item and its descendants, created by `Item::iter`
+///
+/// The iterator borrows the structure and keeps one slice iterator per open
+/// folder, so walking it allocates only as deep as the tree is.
+pub struct ItemIter<'a> {
+    /// The item to yield first, `None` once it has been
+    root: Option<&'a Item>,
+
+    /// Remaining children of each open folder, with their depth
+    stack: Vec<(slice::Iter<'a, Item>, usize)>,
+}
+
+impl<'a> ItemIter<'a> {
+    /// Yields an item, opening its children if it is a folder
+    fn open(&mut self, item: &'a Item, depth: usize) -> (&'a Item, usize) {
+        if let Item::Folder(_, items, ..) = item {
+            self.stack.push((items.iter(), depth + 1));
+        }
+        (item, depth)
+    }
+}
+
+impl<'a> Iterator for ItemIter<'a> {
+    type Item = (&'a Item, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            return Some(self.open(root, 0));
+        }
+
+        loop {
+            let (children, depth) = self.stack.last_mut()?;
+            match children.next() {
+                Some(child) => {
+                    let depth = *depth;
+                    return Some(self.open(child, depth));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
 }
 
 /// Possible errors that can occur during folder structure processing
 impl From<std::io::Error> for FsError {
-    fn from(_: std::io::Error) -> Self {
-        FsError::IoError
+    fn from(error: std::io::Error) -> Self {
+        FsError::IoError(error, None)
     }
 }
 
 /// Expected configuration structure for folder traversal options
 ///
+/// Filters combine: an entry is shown only if it passes every active filter, so an
+/// exclusion by extension, pattern or glob always wins over an inclusion by another one.
+///
 /// # Fields
 /// * `show_empty_folder` - Whether to include empty folders in the output
-/// * `exclude_extension` - List of file extensions to exclude
+/// * `exclude_extension` - List of file extensions to exclude. Entries may span several
+///   dots (e.g. `generated.rs`), and refine `include_extension_only` when both are given
+/// * `include_globs` - Globs files must match to be kept, relative to and anchored at the
+///   traversal root (e.g. `src/**/*.rs`). Folders are not filtered so the tree stays navigable
+/// * `exclude_globs` - Globs of entries to skip, relative to and anchored at the traversal root
+///   (e.g. `**/test_*`)
 /// * `include_extension_only` - List of file extensions to exclusively include
-/// * `exclude_by_filter` - List of regex patterns for excluding items
+/// * `exclude_by_filter` - Regex patterns for excluding items, compiled into a single `RegexSet`
+/// * `filter_mode` - Whether a name matching any `exclude_by_filter` pattern is excluded, or only
+///   one matching all of them. Include patterns are unaffected and still need a single match, and
+///   `exclude_by_path` and `ignore_patterns` still exclude on any match
+/// * `ignore_patterns` - Regex patterns read from an ignore file, excluding a name matching any
+///   of them whatever `filter_mode`, compiled into a single `RegexSet`
+/// * `exclude_by_path` - Regex patterns for excluding items by their path relative to the root,
+///   e.g. `^src/generated$`, compiled into a single `RegexSet`. Paths use `/` separators, without
+///   leading `./` or trailing `/`. They apply alongside `exclude_by_filter`: matching either
+///   excludes the entry, and an excluded folder hides everything below it
+/// * `include_by_filter` - Regex patterns files must match to be kept, compiled into a single
+///   `RegexSet`. Folders are not filtered so the tree stays navigable, and exclude patterns
+///   still apply on top
+/// * `keep_dirs` - Regex patterns of folder names always displayed with their whole subtree,
+///   even without any file passing the filters, compiled into a single `RegexSet`
+/// * `ignore_case` - Match the include and exclude patterns and globs case-insensitively,
+///   and the `mod_files_only` name comparison. Extensions are always compared case-insensitively
+/// * `active_within` - Only show directories containing a file modified within this window
+/// * `pinned_names` - Names sorted first in their directory, in the given order
+/// * `show_size_percent` - Annotate each entry with its share of the parent's total size
+/// * `skip_reparse` - Do not recurse into Windows reparse points (junctions, symlinks)
+/// * `show_inodes` - Annotate each file with its inode number and hard link count, flagging
+///   files with more than one link (Unix only; elsewhere nothing is shown)
+/// * `group_empty_runs` - Collapse consecutive empty sibling folders into a single line
+/// * `max_entries` - Print at most this many children per folder, followed by a `... and N more`
+///   line. Children are truncated after sorting, so with folders first the hidden ones are files
+/// * `owner_uid` - Only include files owned by this uid (Unix only)
+/// * `not_owner_uid` - Exclude files owned by this uid (Unix only)
+/// * `read_retries` - How many times a failed directory read is retried before giving up
+/// * `check_ext_mismatch` - Flag files whose content disagrees with their extension
+/// * `ext_mismatch_only` - Only include files whose content disagrees with their extension,
+///   flagging them as `check_ext_mismatch` does
+/// * `stop_at_size` - Stop traversing once the collected files reach this many bytes
+/// * `first_only` - Stop traversing as soon as one file passes every filter, leaving
+///   a structure with that single file. Which file is found depends on the listing order
+/// * `highlight_path` - Path of an entry to highlight in the output
+/// * `path_to` - Only keep the ancestors of entries whose relative path ends with this path
+/// * `path_to_siblings` - Also keep the siblings of the matched entries
+/// * `min_children` - Only keep folders with at least this many immediate children, and their ancestors
+/// * `show_type_diversity` - Annotate folders with the number of distinct extensions among their files
+/// * `max_types` - Flag folders whose files have more distinct extensions than this
+/// * `skip_dir_with` - Marker file names; folders containing one are listed but not traversed
+/// * `collapse_dirs` - Folder names listed with their number of entries instead of being traversed,
+///   e.g. `node_modules`. Names are compared case-insensitively with `ignore_case`
+/// * `alignment` - How names are padded so annotations line up in a column
+/// * `depth_column` - Prefix each tree line with its zero-padded depth and relative path
+/// * `depth_column_width` - Width of the relative path column, defaults to the widest path
+/// * `show_depth` - Prefix each tree line with its depth in brackets, the root being depth 0
+/// * `full_path` - Print each entry as its path relative to the root instead of its name
+/// * `mod_files_only` - Only include files whose stem equals their parent folder's name
+/// * `mod_files_ignore_case` - Compare stems and folder names case-insensitively
+/// * `explain` - Annotate each displayed entry with the reason it passed the filters
+/// * `on_skip` - Called with the root-relative path and the reason of each entry left out
+///   of the structure, e.g. to report skips on stderr; `None` to not report them
+/// * `max_depth` - Depth at which folders are listed without traversing their children,
+///   0 showing only the root's immediate children
+/// * `min_depth` - Depth above which entries are not printed, their descendants still are;
+///   the entries at this depth become top lines labelled with their relative path
+/// * `show_hidden` - Include dotfiles and dot-directories
+/// * `ascii` - Draw the tree with ASCII connectors instead of box-drawing characters
+/// * `indent` - Width of each tree level, connectors included; at least 2
+/// * `charset` - Draw the tree with the connectors GNU `tree` uses for this charset,
+///   byte for byte, instead of those selected by `ascii`. Requires the default indent of 4
+/// * `line_width` - Shorten names with an ellipsis so tree lines fit in this many columns,
+///   `None` to never shorten them
+/// * `color` - Color entry names with ANSI codes by kind
+/// * `follow_symlinks` - Traverse symlinked folders, listing a link back to one of
+///   its ancestors as a `(cycle)` leaf
+/// * `min_size` - Smallest file size in bytes to keep; files whose size cannot be read are dropped
+/// * `max_size` - Largest file size in bytes to keep; files whose size cannot be read are dropped
+/// * `min_name_length` - Shortest file name to keep, in characters (Unicode scalar values, not
+///   bytes). Folders are not filtered so the tree stays navigable
+/// * `max_name_length` - Longest file name to keep, in characters, e.g. to find names too long
+///   for another file system
+/// * `newer_than` - Only keep files modified within this window; files whose
+///   modification time cannot be read are dropped
+/// * `older_than` - Only keep files last modified longer ago than this; files whose
+///   modification time cannot be read are dropped
+/// * `strict` - Abort on the first unreadable directory instead of listing it as
+///   a `[permission denied]` or `[unreadable]` leaf
+/// * `jobs` - Number of threads traversing the root's subfolders concurrently. Ignored with
+///   `stop_at_size`, `first_only` and `max_total_entries`, whose results depend on the visiting
+///   order, and with `breadth_first`
+/// * `max_total_entries` - Stop traversing once this many entries have been visited across the
+///   whole tree, keeping what was collected. The root is annotated `[entry limit reached]` and
+///   flagged with `entry_limit_reached`, and is kept even when nothing was collected yet
+/// * `on_progress` - Called with the running count of scanned entries during traversal, then
+///   once more when it ends, e.g. to render progress on stderr; `None` to not report it
+/// * `sort_key` - Attribute entries are sorted by within each folder
+/// * `reverse_sort` - Reverse the order given by `sort_key`
+/// * `sort_case_insensitive` - Compare names by their lowercase form, so `apple` sorts before
+///   `Zebra`. Names differing only in case keep their case-sensitive order
+/// * `grouping` - Whether folders are listed before files, after them, or mixed with them
+/// * `dirs_only` - Drop files from the result, folders keeping their flags and sizes
+/// * `show_summary` - End the tree output with the number of displayed folders and files
+/// * `json_pretty` - Indent the JSON tree and array outputs with 2 spaces instead of
+///   printing them on one line
+/// * `print_root_path` - Name the root after its canonical absolute path instead of its basename
+/// * `root_name` - Label printed for the root instead of its basename (or `.`), taking
+///   precedence over `print_root_path`
+/// * `respect_gitignore` - Skip entries ignored by the `.gitignore` files found during traversal
+/// * `show_size` - Show each file's size in human-readable units
+/// * `show_folder_size` - Also show each folder's total size when sizes are shown
+/// * `du` - Start each tree line with the entry's size, folders totalling their descendant
+///   files, like `du`
+/// * `show_perms` - Start each tree line with the entry's permissions in `ls -l` style, e.g.
+///   `rwxr-xr-x`, after the `du` column. Entries whose permissions are unknown, including every
+///   entry outside Unix, show `?????????`
+/// * `mtime_style` - Start each tree line with the entry's last modification time in this
+///   style, after the `du` and permissions columns; `None` to not show it. Folders show their
+///   most recent file, and entries whose time is unknown show `-`
+/// * `breadth_first` - Traverse level by level with a queue instead of recursing, so
+///   shallow entries are visited first when the traversal is stopped early
 #[derive(Builder)]
 #[builder(build_fn(validate = "Self::validate"))]
 pub struct FolderStructureOptions {
     #[builder(default = "Vec::new()")]
     exclude_extension: Vec<String>,
 
-    #[builder(default = "Vec::new()")]
-    exclude_by_filter: Vec<Regex>,
+    #[builder(field(ty = "Vec<Regex>", build = "build_regex_set(&self.exclude_by_filter, self.ignore_case.unwrap_or(false))?"))]
+    exclude_by_filter: RegexSet,
+
+    #[builder(field(ty = "Vec<Regex>", build = "build_regex_set(&self.ignore_patterns, self.ignore_case.unwrap_or(false))?"))]
+    ignore_patterns: RegexSet,
+
+    #[builder(default = "FilterMode::Any")]
+    filter_mode: FilterMode,
+
+    #[builder(field(ty = "Vec<Regex>", build = "build_regex_set(&self.exclude_by_path, self.ignore_case.unwrap_or(false))?"))]
+    exclude_by_path: RegexSet,
+
+    #[builder(field(ty = "Vec<Regex>", build = "build_regex_set(&self.include_by_filter, self.ignore_case.unwrap_or(false))?"))]
+    include_by_filter: RegexSet,
+
+    #[builder(field(ty = "Vec<Regex>", build = "build_regex_set(&self.keep_dirs, self.ignore_case.unwrap_or(false))?"))]
+    keep_dirs: RegexSet,
+
+    #[builder(default = "false")]
+    ignore_case: bool,
 
     #[builder(default = "Vec::new()")]
     include_extension_only: Vec<String>,
 
+    #[builder(field(ty = "Vec<String>", build = "build_glob_set(&self.include_globs, self.ignore_case.unwrap_or(false))?"))]
+    include_globs: RegexSet,
+
+    #[builder(field(ty = "Vec<String>", build = "build_glob_set(&self.exclude_globs, self.ignore_case.unwrap_or(false))?"))]
+    exclude_globs: RegexSet,
+
     #[builder(default = "false")]
     show_empty_folder: bool,
-}
 
-/// Validates the configuration options for folder structure.
-///
-/// # Errors
-///
-/// Returns an error if both `exclude_extension` and `include_extension_only` are non-empty,
-/// as these options are mutually exclusive.
-///
-/// Returns `Ok(())` if the validation passes.
-impl FolderStructureOptionsBuilder {
-    fn validate(&self) -> Result<(), String> {
-        if !self.exclude_extension.as_ref().unwrap_or(&vec![]).is_empty() 
-            && !self.include_extension_only.as_ref().unwrap_or(&vec![]).is_empty() {
-            return Err("Cannot specify both exclude_extension and include_extension_only".to_string());
-        }
-        Ok(())
-    }
+    #[builder(default = "None")]
+    active_within: Option<Duration>,
+
+    #[builder(default = "Vec::new()")]
+    pinned_names: Vec<String>,
+
+    #[builder(default = "false")]
+    show_size_percent: bool,
+
+    #[builder(default = "false")]
+    skip_reparse: bool,
+
+    #[builder(default = "false")]
+    show_inodes: bool,
+
+    #[builder(default = "false")]
+    group_empty_runs: bool,
+
+    #[builder(default = "None")]
+    max_entries: Option<usize>,
+
+    #[builder(default = "None")]
+    owner_uid: Option<u32>,
+
+    #[builder(default = "None")]
+    not_owner_uid: Option<u32>,
+
+    #[builder(default = "0")]
+    read_retries: u32,
+
+    #[builder(default = "false")]
+    check_ext_mismatch: bool,
+
+    #[builder(default = "false")]
+    ext_mismatch_only: bool,
+
+    #[builder(default = "None")]
+    stop_at_size: Option<u64>,
+
+    #[builder(default = "false")]
+    first_only: bool,
+
+    #[builder(default = "None")]
+    max_total_entries: Option<usize>,
+
+    #[builder(default = "None")]
+    highlight_path: Option<PathBuf>,
+
+    #[builder(default = "None")]
+    path_to: Option<String>,
+
+    #[builder(default = "false")]
+    path_to_siblings: bool,
+
+    #[builder(default = "None")]
+    min_children: Option<usize>,
+
+    #[builder(default = "false")]
+    show_type_diversity: bool,
+
+    #[builder(default = "None")]
+    max_types: Option<usize>,
+
+    #[builder(default = "Vec::new()")]
+    skip_dir_with: Vec<String>,
+
+    #[builder(default = "Vec::new()")]
+    collapse_dirs: Vec<String>,
+
+    #[builder(default = "None")]
+    alignment: Option<Alignment>,
+
+    #[builder(default = "false")]
+    depth_column: bool,
+
+    #[builder(default = "None")]
+    depth_column_width: Option<usize>,
+
+    #[builder(default = "false")]
+    show_depth: bool,
+
+    #[builder(default = "false")]
+    full_path: bool,
+
+    #[builder(default = "false")]
+    mod_files_only: bool,
+
+    #[builder(default = "false")]
+    mod_files_ignore_case: bool,
+
+    #[builder(default = "false")]
+    explain: bool,
+
+    #[builder(default = "None")]
+    on_skip: Option<SkipCallback>,
+
+    #[builder(default = "None")]
+    max_depth: Option<usize>,
+
+    #[builder(default = "0")]
+    min_depth: usize,
+
+    #[builder(default = "false")]
+    breadth_first: bool,
+
+    #[builder(default = "false")]
+    show_size: bool,
+
+    #[builder(default = "false")]
+    show_folder_size: bool,
+
+    #[builder(default = "false")]
+    du: bool,
+
+    #[builder(default = "false")]
+    show_perms: bool,
+
+    #[builder(default = "None")]
+    mtime_style: Option<TimeStyle>,
+
+    #[builder(default = "false")]
+    respect_gitignore: bool,
+
+    #[builder(default = "false")]
+    show_hidden: bool,
+
+    #[builder(default = "false")]
+    color: bool,
+
+    #[builder(default = "false")]
+    ascii: bool,
+
+    #[builder(default = "4")]
+    indent: usize,
+
+    #[builder(default = "None")]
+    charset: Option<Charset>,
+
+    #[builder(default = "None")]
+    line_width: Option<usize>,
+
+    #[builder(default = "true")]
+    show_summary: bool,
+
+    #[builder(default = "false")]
+    pub(crate) json_pretty: bool,
+
+    #[builder(default = "false")]
+    print_root_path: bool,
+
+    #[builder(default = "None")]
+    root_name: Option<String>,
+
+    #[builder(default = "false")]
+    follow_symlinks: bool,
+
+    #[builder(default = "1")]
+    jobs: usize,
+
+    #[builder(default = "None")]
+    on_progress: Option<ProgressCallback>,
+
+    #[builder(default = "false")]
+    strict: bool,
+
+    #[builder(default = "None")]
+    min_size: Option<u64>,
+
+    #[builder(default = "None")]
+    max_size: Option<u64>,
+
+    #[builder(default = "None")]
+    min_name_length: Option<usize>,
+
+    #[builder(default = "None")]
+    max_name_length: Option<usize>,
+
+    #[builder(default = "None")]
+    newer_than: Option<Duration>,
+
+    #[builder(default = "None")]
+    older_than: Option<Duration>,
+
+    #[builder(default = "false")]
+    dirs_only: bool,
+
+    #[builder(default = "SortKey::Name")]
+    sort_key: SortKey,
+
+    #[builder(default = "false")]
+    reverse_sort: bool,
+
+    #[builder(default = "false")]
+    sort_case_insensitive: bool,
+
+    #[builder(default = "Grouping::DirsFirst")]
+    grouping: Grouping,
 }
 
-/// Gets the complete folder structure starting from the given path
-///
-/// # Arguments
-/// * `path` - The starting path to generate the structure from
-/// * `options` - Configuration options for filtering and display
-///
-/// # Returns
-/// * `FsResult<Item>` - The resulting folder structure or an error
-pub fn get_folder_structure(path: &PathBuf, options: &FolderStructureOptions) -> FsResult<Item> {
-    let name = get_path_name(path);
+/// How names are padded so that annotations line up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    /// Align annotations among the entries of each directory
+    PerDirectory,
 
-    if path.is_file() {
-        return handle_file(name, options);
-    }
+    /// Align annotations across the whole tree
+    Global,
 
-    let items = process_directory(path, options)?;
-    let mut folder = create_folder_item(path, name, items, options)?;
-    update_has_terminal_file(&mut folder);
-    Ok(folder)
+    /// Align annotations at a fixed column
+    Width(usize),
 }
 
-/// Prints the complete folder structure as a tree
-///
+impl FromStr for Alignment {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "dir" => Ok(Alignment::PerDirectory),
+            "global" => Ok(Alignment::Global),
+            _ => value.parse()
+                .map(Alignment::Width)
+                .map_err(|_| format!("Invalid alignment '{}' (expected dir, global or a column width)", value)),
+        }
+    }
+}
+
+/// Attribute entries are ordered by within their directory
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortKey {
+    /// Alphabetical order
+    #[default]
+    Name,
+
+    /// Largest first, folders by their total size
+    Size,
+
+    /// Most recently modified first, folders by their latest file
+    Modified,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "name" => Ok(SortKey::Name),
+            "size" => Ok(SortKey::Size),
+            "mtime" => Ok(SortKey::Modified),
+            _ => Err(format!("Invalid sort key '{}' (expected name, size or mtime)", value)),
+        }
+    }
+}
+
+/// How modification times are printed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TimeStyle {
+    /// `YYYY-MM-DD HH:MM` in UTC
+    #[default]
+    Iso,
+
+    /// Time elapsed since, e.g. `3 days ago`
+    Relative,
+}
+
+impl FromStr for TimeStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "iso" => Ok(TimeStyle::Iso),
+            "relative" => Ok(TimeStyle::Relative),
+            _ => Err(format!("Invalid time style '{}' (expected iso or relative)", value)),
+        }
+    }
+}
+
+/// How the exclude patterns combine
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FilterMode {
+    /// A name matching any pattern is excluded
+    #[default]
+    Any,
+
+    /// A name is excluded only if it matches every pattern
+    All,
+}
+
+impl FromStr for FilterMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "any" => Ok(FilterMode::Any),
+            "all" => Ok(FilterMode::All),
+            _ => Err(format!("Invalid filter mode '{}' (expected any or all)", value)),
+        }
+    }
+}
+
+/// How folders and files are grouped within their directory, before the sort key applies
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Grouping {
+    /// Folders before files
+    #[default]
+    DirsFirst,
+
+    /// Files before folders
+    DirsLast,
+
+    /// Folders and files ordered together by the sort key alone
+    Mixed,
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "dirs-first" => Ok(Grouping::DirsFirst),
+            "dirs-last" => Ok(Grouping::DirsLast),
+            "mixed" => Ok(Grouping::Mixed),
+            _ => Err(format!("Invalid grouping '{}' (expected dirs-first, dirs-last or mixed)", value)),
+        }
+    }
+}
+
+/// Connector set of GNU `tree`'s `--charset` option
+///
+/// Each set gives the connectors of an entry, of the last entry, and the
+/// prefix continuing a folder's line, the prefix below a last entry being
+/// four spaces in both:
+/// - `utf8`: `├── `, `└── ` and `│` followed by two no-break spaces (U+00A0) and a space
+/// - `ascii`: `|-- `, `` `-- `` and `|   `
+///
+/// Only the connectors follow GNU `tree`; the layout around them, such as the
+/// indented top level and the `/` after folder names, stays the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Charset {
+    /// Box-drawing characters, as printed in a UTF-8 locale
+    Utf8,
+
+    /// Plain ASCII, GNU `tree`'s fallback for charsets it has no table for
+    Ascii,
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(Charset::Utf8),
+            "ascii" | "us-ascii" => Ok(Charset::Ascii),
+            _ => Err(format!("Unsupported charset '{}' (expected utf8 or ascii)", value)),
+        }
+    }
+}
+
+/// Metadata of an entry, as read from a `DirSource`
+///
+/// Values a platform or source cannot provide are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryMetadata {
+    /// Whether the entry is a regular file
+    pub is_file: bool,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Last modification time
+    pub modified: Option<SystemTime>,
+
+    /// Permission bits, without the file type
+    pub mode: Option<u32>,
+
+    /// User id of the owner
+    pub uid: Option<u32>,
+
+    /// Inode number
+    pub inode: Option<u64>,
+
+    /// Number of hard links to the inode
+    pub hard_links: Option<u64>,
+}
+
+impl From<&fs::Metadata> for EntryMetadata {
+    #[cfg(unix)]
+    fn from(metadata: &fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        EntryMetadata {
+            is_file: metadata.is_file(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            mode: Some(metadata.mode() & 0o7777),
+            uid: Some(metadata.uid()),
+            inode: Some(metadata.ino()),
+            hard_links: Some(metadata.nlink()),
+        }
+    }
+
+    // Permissions, owners and inodes are only exposed on Unix
+    #[cfg(not(unix))]
+    fn from(metadata: &fs::Metadata) -> Self {
+        EntryMetadata {
+            is_file: metadata.is_file(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            ..EntryMetadata::default()
+        }
+    }
+}
+
+/// File system access used by the traversal
+///
+/// `get_folder_structure` reads the real file system through `RealFs`, while
+/// `get_folder_structure_from_source` accepts any implementation, e.g. a
+/// `MemoryFs` to exercise the filters without temporary directories.
+/// Features reading file contents or link targets, such as `.gitignore` files
+/// or `check_ext_mismatch`, still go to the real file system.
+pub trait DirSource: Sync {
+    /// Lists the paths of a directory's entries, in the order they are visited
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>>;
+
+    /// Checks whether a path is a directory, following symlinks
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Checks whether a path exists, without following symlinks
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Reads a path's metadata following symlinks, `None` if it is not available
+    ///
+    /// Files without metadata have no size, time, owner or permissions, so the
+    /// size, age and owner filters drop them.
+    fn metadata(&self, path: &Path) -> Option<EntryMetadata>;
+}
+
+/// The `DirSource` reading the real file system
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFs;
+
+impl DirSource for RealFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+        Ok(fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok()
+    }
+
+    fn metadata(&self, path: &Path) -> Option<EntryMetadata> {
+        fs::metadata(path).ok().as_ref().map(EntryMetadata::from)
+    }
+}
+
+/// A `DirSource` holding a tree of entries in memory
+///
+/// Directories list their entries in the order they were added. Adding an
+/// entry also adds its missing ancestors as directories.
+///
+/// # Example
+/// ```
+/// use std::path::Path;
+/// use folder_clip_cli::{get_folder_structure_from_source, EntryMetadata, FolderStructureOptionsBuilder, MemoryFs};
+///
+/// let mut source = MemoryFs::new();
+/// source.add_file("root/src/main.rs", EntryMetadata { size: 12, ..EntryMetadata::default() });
+/// let options = FolderStructureOptionsBuilder::default().build().unwrap();
+/// let root = get_folder_structure_from_source(Path::new("root"), &options, &source)?;
+/// assert_eq!(root.iter().count(), 3);
+/// # Ok::<(), folder_clip_cli::FsError>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFs {
+    /// Metadata of every entry, keyed by path
+    entries: HashMap<PathBuf, EntryMetadata>,
+
+    /// Paths of the entries of every directory, in insertion order
+    children: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl MemoryFs {
+    /// Creates an empty tree
+    pub fn new() -> Self {
+        MemoryFs::default()
+    }
+
+    /// Adds a directory, along with its missing ancestors
+    ///
+    /// # Arguments
+    /// * `path` - Path of the directory
+    ///
+    /// # Returns
+    /// * `&mut Self` - The tree, to chain further additions
+    pub fn add_dir(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        let path = path.as_ref();
+        if self.children.contains_key(path) {
+            return self;
+        }
+        self.insert(path, EntryMetadata::default());
+        self.children.insert(path.to_path_buf(), Vec::new());
+        self
+    }
+
+    /// Adds a regular file, along with its missing ancestors
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file
+    /// * `metadata` - Metadata reported for the file, `is_file` being set regardless
+    ///
+    /// # Returns
+    /// * `&mut Self` - The tree, to chain further additions
+    pub fn add_file(&mut self, path: impl AsRef<Path>, metadata: EntryMetadata) -> &mut Self {
+        self.insert(path.as_ref(), EntryMetadata { is_file: true, ..metadata });
+        self
+    }
+
+    /// Records an entry and links it to its parent directory
+    fn insert(&mut self, path: &Path, metadata: EntryMetadata) {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            self.add_dir(parent);
+            let siblings = self.children.entry(parent.to_path_buf()).or_default();
+            if !siblings.iter().any(|sibling| sibling == path) {
+                siblings.push(path.to_path_buf());
+            }
+        }
+        self.entries.insert(path.to_path_buf(), metadata);
+    }
+}
+
+impl DirSource for MemoryFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+        match self.children.get(path) {
+            Some(children) => Ok(children.iter().cloned().map(Ok).collect()),
+            None if self.entries.contains_key(path) => Err(io::Error::other("not a directory")),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.children.contains_key(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Option<EntryMetadata> {
+        self.entries.get(path).cloned()
+    }
+}
+
+/// Mutable state shared across a single traversal
+struct TraversalState<'a> {
+    /// Where directory listings and metadata are read from
+    source: &'a dyn DirSource,
+
+    /// Total size of the files collected so far
+    collected_size: u64,
+
+    /// Whether the traversal was stopped before visiting every entry
+    stopped: bool,
+
+    /// Depth of the entries being listed, 0 for the root's immediate children
+    depth: usize,
+
+    /// Path the traversal started from
+    root: PathBuf,
+
+    /// Parsed `.gitignore` of each directory visited so far, `None` if it has none
+    gitignores: HashMap<PathBuf, Option<Gitignore>>,
+
+    /// Canonical paths of the folders being traversed, from the root down to the current
+    /// one, tracked when following symlinks
+    ancestors: Vec<PathBuf>,
+
+    /// Number of entries visited so far, shared with the parallel workers
+    scanned: Arc<AtomicUsize>,
+}
+
+impl<'a> TraversalState<'a> {
+    /// Creates the state of a traversal starting at `root`
+    fn new(root: &Path, source: &'a dyn DirSource) -> Self {
+        TraversalState {
+            source,
+            collected_size: 0,
+            stopped: false,
+            depth: 0,
+            root: root.to_path_buf(),
+            gitignores: HashMap::new(),
+            ancestors: Vec::new(),
+            scanned: Arc::default(),
+        }
+    }
+}
+
+/// Number of scanned entries between two progress updates
+pub const PROGRESS_INTERVAL: usize = 1000;
+
+/// A line to print among a folder's children
+enum ChildEntry<'a> {
+    /// A regular item
+    Item(&'a Item),
+
+    /// A run of consecutive empty folders collapsed into one line
+    EmptyRun(usize),
+}
+
+/// Validates the configuration options for folder structure.
+///
+/// # Errors
+///
+/// Returns an error if an extension is both in `include_extension_only` and in
+/// `exclude_extension`, or if an included multi-dot entry ends with an excluded
+/// one (`generated.rs` with `rs`), since that entry could never match. Other
+/// combinations are valid: the exclude list refines the include list.
+///
+/// Returns `Ok(())` if the validation passes.
+impl FolderStructureOptionsBuilder {
+    fn validate(&self) -> Result<(), String> {
+        let normalize = |ext: &String| ext.trim_start_matches('.').to_lowercase();
+        let excluded = self.exclude_extension.iter().flatten().map(normalize).collect::<HashSet<_>>();
+        if let Some(ext) = self.include_extension_only.iter().flatten().find(|ext| excluded.contains(&normalize(ext))) {
+            return Err(format!("Extension '{}' cannot be both included and excluded", ext));
+        }
+        // Every name ending with `.generated.rs` also has the `rs` extension
+        for ext in self.include_extension_only.iter().flatten() {
+            let included = normalize(ext);
+            if let Some(covering) = excluded.iter().find(|excluded| included.ends_with(&format!(".{}", excluded))) {
+                return Err(format!("Extension '{}' is always excluded by '{}'", ext, covering));
+            }
+        }
+        if self.indent.is_some_and(|indent| indent < 2) {
+            return Err("indent must be at least 2".to_string());
+        }
+        if matches!(self.charset, Some(Some(_))) && self.indent.is_some_and(|indent| indent != 4) {
+            return Err("charset connectors are 4 characters wide and cannot be combined with another indent".to_string());
+        }
+        if let (Some(Some(min)), Some(Some(max))) = (self.min_size, self.max_size) {
+            if min > max {
+                return Err(format!("min_size ({}) cannot exceed max_size ({})", min, max));
+            }
+        }
+        if let (Some(Some(min)), Some(Some(max))) = (self.min_name_length, self.max_name_length) {
+            if min > max {
+                return Err(format!("min_name_length ({}) cannot exceed max_name_length ({})", min, max));
+            }
+        }
+        // Files must be modified after `now - newer_than` and before `now - older_than`
+        if let (Some(Some(newer)), Some(Some(older))) = (self.newer_than, self.older_than) {
+            if older >= newer {
+                return Err(format!(
+                    "older_than ({}s) must be shorter than newer_than ({}s), or no file can match",
+                    older.as_secs(),
+                    newer.as_secs()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compiles individually validated regexes into one `RegexSet`
+///
+/// Matching a name against the set is a single pass no matter how many
+/// patterns it holds, and matches exactly when any of the regexes would.
+///
+/// # Arguments
+/// * `regexes` - The regexes to combine
+/// * `ignore_case` - Whether the set matches case-insensitively
+///
+/// # Returns
+/// * `Result<RegexSet, String>` - The compiled set or the compilation error
+fn build_regex_set(regexes: &[Regex], ignore_case: bool) -> Result<RegexSet, String> {
+    RegexSetBuilder::new(regexes.iter().map(Regex::as_str))
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Compiles globs into a single `RegexSet` matching root-relative paths
+///
+/// # Arguments
+/// * `globs` - The globs to compile, a leading `/` being ignored
+/// * `ignore_case` - Whether the set matches case-insensitively
+///
+/// # Returns
+/// * `Result<RegexSet, String>` - The compiled set or the compilation error
+fn build_glob_set(globs: &[String], ignore_case: bool) -> Result<RegexSet, String> {
+    RegexSetBuilder::new(globs.iter().map(|glob| glob_to_regex(glob.trim_start_matches('/'))))
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Gets the complete folder structure starting from the given path
+///
+/// When `stop_at_size` is set, the traversal stops as soon as the collected
+/// files reach the budget and the root is annotated `[size limit reached]`.
+/// Which files are collected then depends on the order in which the file
+/// system lists directory entries.
+///
+/// # Arguments
+/// * `path` - The starting path to generate the structure from
+/// * `options` - Configuration options for filtering and display
+///
+/// # Returns
+/// * `FsResult<Item>` - The resulting folder structure or an error
+pub fn get_folder_structure(path: &Path, options: &FolderStructureOptions) -> FsResult<Item> {
+    get_folder_structure_from_source(path, options, &RealFs)
+}
+
+/// Gets the complete folder structure starting from the given path, reading it from `source`
+///
+/// Behaves like `get_folder_structure`, which uses the real file system.
+///
+/// # Arguments
+/// * `path` - The starting path to generate the structure from
+/// * `options` - Configuration options for filtering and display
+/// * `source` - Where directory listings and metadata are read from
+///
+/// # Returns
+/// * `FsResult<Item>` - The resulting folder structure or an error
+pub fn get_folder_structure_from_source(path: &Path, options: &FolderStructureOptions, source: &dyn DirSource) -> FsResult<Item> {
+    let mut state = TraversalState::new(path, source);
+    let built = if options.breadth_first && is_traversable(path, source) {
+        build_structure_breadth_first(path, options, &mut state)
+    } else {
+        build_structure(path, options, &mut state)
+    };
+    if let Some(on_progress) = &options.on_progress {
+        on_progress(Progress::Done(state.scanned.load(atomic::Ordering::Relaxed)));
+    }
+
+    // A capped traversal may stop before any file is collected, which is a truncated
+    // result rather than a root without matches
+    let entry_limit = entry_limit_reached(options, &state);
+    let mut root = match built {
+        Err(FsError::EmptyFolder) if entry_limit => {
+            let name = if path.as_os_str() == "." { ".".to_string() } else { get_path_name(path) };
+            create_truncated_folder(name)
+        }
+        built => built?,
+    };
+
+    let limit_note = if entry_limit {
+        Some("entry limit reached")
+    } else if state.stopped && options.stop_at_size.is_some() {
+        Some("size limit reached")
+    } else {
+        None
+    };
+    if let (Some(note), Item::Folder(.., metadata)) = (limit_note, &mut root) {
+        metadata.note = Some(note.to_string());
+        metadata.entry_limit_reached = entry_limit;
+    }
+
+    if let Some(label) = &options.root_name {
+        let (Item::File(name, _) | Item::Folder(name, ..)) = &mut root;
+        *name = label.clone();
+    } else if options.print_root_path {
+        let (Item::File(name, _) | Item::Folder(name, ..)) = &mut root;
+        *name = root_path_label(path);
+    }
+
+    update_has_terminal_file(&mut root);
+    if !options.keep_dirs.is_empty() {
+        mark_kept_dirs(&mut root, false, options);
+    }
+    update_latest_modified(&mut root);
+    update_total_size(&mut root);
+
+    // Sorting once everything is built keeps the order independent of the traversal
+    // strategy and lets folders be compared by their aggregated size and time
+    sort_structure(&mut root, options);
+
+    if let Some(window) = options.active_within {
+        let threshold = SystemTime::now().checked_sub(window).unwrap_or(SystemTime::UNIX_EPOCH);
+        if let Item::Folder(_, items, ..) = &mut root {
+            retain_active_dirs(items, threshold);
+        }
+    }
+
+    if let Some(min_children) = options.min_children {
+        retain_crowded_dirs(&mut root, min_children);
+    }
+
+    if let Some(target) = &options.path_to {
+        let target = target.split(['/', '\\'])
+            .filter(|component| !component.is_empty() && *component != ".")
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let found = match &mut root {
+            Item::Folder(_, items, ..) => retain_target_spines(items, &mut Vec::new(), &target, options.path_to_siblings),
+            Item::File(..) => false,
+        };
+        if !found {
+            return Err(FsError::NoMatch);
+        }
+    }
+
+    if options.dirs_only {
+        remove_files(&mut root);
+    }
+
+    Ok(root)
+}
+
+/// Checks whether the traversal was stopped by `max_total_entries`
+///
+/// # Arguments
+/// * `options` - Configuration options containing the limit
+/// * `state` - State of the finished traversal
+///
+/// # Returns
+/// * `bool` - Whether the limit was reached
+fn entry_limit_reached(options: &FolderStructureOptions, state: &TraversalState) -> bool {
+    let scanned = state.scanned.load(atomic::Ordering::Relaxed);
+    options.max_total_entries.is_some_and(|max| scanned > max)
+}
+
+/// An event reported by `walk` as the traversal proceeds
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // Handed straight to the callback, boxing would allocate per file
+pub enum WalkEvent<'a> {
+    /// A folder is entered. Its entries are reported next, then the matching `LeaveDir`
+    EnterDir {
+        /// Path to the folder
+        path: &'a Path,
+
+        /// Depth of the folder, 0 for the root
+        depth: usize,
+
+        /// Whether the folder is the last entry of its parent
+        is_last: bool,
+    },
+
+    /// A file, or a folder listed without its children such as a collapsed,
+    /// unreadable or `max_depth` one, reported as an `Item::Folder` without items
+    File {
+        /// Path to the entry
+        path: &'a Path,
+
+        /// The entry, with the metadata captured for it
+        item: Item,
+
+        /// Depth of the entry, 0 for the root
+        depth: usize,
+
+        /// Whether the entry is the last of its parent
+        is_last: bool,
+    },
+
+    /// Every entry of the folder entered at `path` has been reported
+    LeaveDir {
+        /// Path to the folder
+        path: &'a Path,
+
+        /// Depth of the folder, 0 for the root
+        depth: usize,
+    },
+}
+
+/// Traverses a path, reporting entries to `visit` as they are found instead of building a tree
+///
+/// Only the entries of the folders on the current path are held in memory, so
+/// huge trees can be processed without storing them. The entries of a folder
+/// are read and filtered before it is entered, and reported sorted as with
+/// `get_folder_structure`, except that subfolders compare as empty for the size
+/// and modification time keys since their contents are not known yet.
+///
+/// A folder is reported before its children, so whether it ends up empty is
+/// not known when it is entered: every traversed folder is reported, as if
+/// `show_empty_folder` were set. Passes needing the whole tree are not applied
+/// either, such as `keep_dirs`, `active_within`, `min_children` and `path_to`,
+/// and folders have no aggregated size or time. With `dirs_only`, files are
+/// filtered as usual but not reported.
+///
+/// # Arguments
+/// * `path` - The starting path to traverse
+/// * `options` - Configuration options for filtering
+/// * `visit` - Called with each event, in traversal order; returning `ControlFlow::Break`
+///   stops the traversal without any further event
+///
+/// # Returns
+/// * `FsResult<bool>` - Whether `max_total_entries` stopped the traversal before every entry
+///   was visited, or the first error that stopped it
+pub fn walk<F: FnMut(WalkEvent) -> ControlFlow<()>>(path: &Path, options: &FolderStructureOptions, mut visit: F) -> FsResult<bool> {
+    let mut state = TraversalState::new(path, &RealFs);
+    let result = if is_traversable(path, state.source) {
+        // Whether the callback stopped the traversal makes no difference once it is over
+        with_ancestor(path, options, &mut state, |state| walk_directory(path, true, options, state, &mut visit)).map(|_| ())
+    } else {
+        handle_file(path, get_path_name(path), options, &mut state).map(|item| {
+            let _ = visit(WalkEvent::File { path, item, depth: 0, is_last: true });
+        })
+    };
+    if let Some(on_progress) = &options.on_progress {
+        on_progress(Progress::Done(state.scanned.load(atomic::Ordering::Relaxed)));
+    }
+    result.map(|_| entry_limit_reached(options, &state))
+}
+
+/// Reports a folder and its entries, recursing into its subfolders
+///
+/// # Arguments
+/// * `path` - Path to the folder
+/// * `is_last` - Whether the folder is the last entry of its parent
+/// * `options` - Configuration options for filtering
+/// * `state` - State shared across the traversal, its depth being the folder's
+/// * `visit` - Called with each event
+///
+/// # Returns
+/// * `FsResult<ControlFlow<()>>` - Whether `visit` stopped the traversal, or the first error
+fn walk_directory<F: FnMut(WalkEvent) -> ControlFlow<()>>(path: &Path, is_last: bool, options: &FolderStructureOptions, state: &mut TraversalState, visit: &mut F) -> FsResult<ControlFlow<()>> {
+    let depth = state.depth;
+    let entries = match read_dir_with_retry(path, options.read_retries, state.source) {
+        Ok(entries) => entries,
+        Err(error) if !options.strict && depth > 0 => {
+            let item = with_mode(create_marked_folder(get_path_name(path), unreadable_note(error.kind())), path, options);
+            return Ok(visit(WalkEvent::File { path, item, depth, is_last }));
+        }
+        Err(error) => return Err(FsError::at(error, path)),
+    };
+
+    // Subfolders to descend into get an empty placeholder item to be sorted with their siblings
+    let mut children = Vec::new();
+    for entry in entries {
+        if state.stopped {
+            break;
+        }
+
+        let child = match entry {
+            Ok(child) => child,
+            Err(_) if !options.strict => continue,
+            Err(e) => return Err(FsError::at(e, path)),
+        };
+        match classify_entry(&child, options, state) {
+            EntryAction::Skip => {}
+            EntryAction::Leaf(item) => children.push((with_mode(item, &child, options), false, child)),
+            EntryAction::Descend if is_traversable(&child, state.source) => {
+                let placeholder = Item::Folder(get_path_name(&child), Vec::new(), None, ItemMetadata::default());
+                children.push((placeholder, true, child));
+            }
+            EntryAction::Descend => match handle_file(&child, get_path_name(&child), options, state) {
+                Ok(item) => children.push((mark_highlighted(item, &child, options), false, child)),
+                Err(FsError::Filtered) => {}
+                Err(e) => return Err(e),
+            },
+        }
+    }
+    if options.dirs_only {
+        children.retain(|(item, ..)| matches!(item, Item::Folder(..)));
+    }
+    children.sort_by(|(a, ..), (b, ..)| sort_items(a, b, options));
+
+    if visit(WalkEvent::EnterDir { path, depth, is_last }).is_break() {
+        return Ok(ControlFlow::Break(()));
+    }
+    let count = children.len();
+    for (index, (item, descend, child)) in children.into_iter().enumerate() {
+        let is_last = index + 1 == count;
+        let flow = if descend {
+            state.depth += 1;
+            let result = with_ancestor(&child, options, state, |state| walk_directory(&child, is_last, options, state, visit));
+            state.depth -= 1;
+            result?
+        } else {
+            visit(WalkEvent::File { path: &child, item, depth: depth + 1, is_last })
+        };
+        if flow.is_break() {
+            return Ok(flow);
+        }
+    }
+    Ok(visit(WalkEvent::LeaveDir { path, depth }))
+}
+
+/// Finds the nearest ancestor of a path containing the given marker entry
+///
+/// The search starts at the path itself and walks up to the file system root.
+///
+/// # Arguments
+/// * `path` - The path to start from
+/// * `marker` - Name of the entry to look for, e.g. `.git` or `Cargo.toml`
+///
+/// # Returns
+/// * `Option<PathBuf>` - The canonicalized ancestor, `None` if no ancestor has the marker
+pub fn find_marker_ancestor(path: &Path, marker: &str) -> Option<PathBuf> {
+    let start = path.canonicalize().ok()?;
+    start.ancestors()
+        .find(|ancestor| ancestor.join(marker).exists())
+        .map(Path::to_path_buf)
+}
+
+/// Recursively builds the structure for a path without any post-processing
+///
+/// # Arguments
+/// * `path` - The path to build the structure from
+/// * `options` - Configuration options for filtering
+/// * `state` - State shared across the traversal
+///
+/// # Returns
+/// * `FsResult<Item>` - The resulting item or an error
+fn build_structure(path: &Path, options: &FolderStructureOptions, state: &mut TraversalState) -> FsResult<Item> {
+    let name = get_path_name(path);
+
+    let item = if is_traversable(path, state.source) {
+        let items = with_ancestor(path, options, state, |state| process_directory(path, options, state))?;
+        match create_folder_item(path, name, items, options) {
+            // An empty root is reported by the caller
+            Err(FsError::EmptyFolder) if path != state.root => {
+                skip_entry(path, ExclusionReason::EmptyFolder, options, state);
+                return Err(FsError::EmptyFolder);
+            }
+            result => result?,
+        }
+    } else {
+        handle_file(path, name, options, state)?
+    };
+
+    Ok(mark_highlighted(item, path, options))
+}
+
+/// Builds the structure for an entry of a directory, tolerating unreadable folders
+///
+/// Unless `strict` is set, a folder that cannot be read is listed without its
+/// contents, annotated with the reason, so its siblings are still traversed.
+///
+/// # Arguments
+/// * `path` - The path to build the structure from
+/// * `options` - Configuration options for filtering
+/// * `state` - State shared across the traversal
+///
+/// # Returns
+/// * `FsResult<Item>` - The resulting item or an error
+fn build_child(path: &Path, options: &FolderStructureOptions, state: &mut TraversalState) -> FsResult<Item> {
+    match build_structure(path, options, state) {
+        Err(FsError::IoError(error, _)) if !options.strict => {
+            Ok(with_mode(create_marked_folder(get_path_name(path), unreadable_note(error.kind())), path, options))
+        }
+        result => result,
+    }
+}
+
+/// Traverses a folder with its canonical path on the chain of ancestors, when following symlinks
+///
+/// # Arguments
+/// * `path` - Path to the folder
+/// * `options` - Configuration options deciding whether symlinks are followed
+/// * `state` - State holding the chain of ancestors
+/// * `traverse` - Traverses the folder's entries
+///
+/// # Returns
+/// * `T` - What `traverse` returned
+fn with_ancestor<'a, T>(path: &Path, options: &FolderStructureOptions, state: &mut TraversalState<'a>, traverse: impl FnOnce(&mut TraversalState<'a>) -> T) -> T {
+    let pushed = options.follow_symlinks && path.canonicalize().map(|canonical| state.ancestors.push(canonical)).is_ok();
+    let result = traverse(state);
+    if pushed {
+        state.ancestors.pop();
+    }
+    result
+}
+
+/// Checks whether a path is traversed as a folder
+///
+/// Anything else that exists, including broken links and special files, is
+/// listed as a file. Missing paths count as folders so reading them reports the error.
+fn is_traversable(path: &Path, source: &dyn DirSource) -> bool {
+    source.is_dir(path) || !source.exists(path)
+}
+
+/// Describes why a folder could not be read, for its note
+fn unreadable_note(kind: io::ErrorKind) -> &'static str {
+    match kind {
+        io::ErrorKind::PermissionDenied => "permission denied",
+        _ => "unreadable",
+    }
+}
+
+/// Builds the structure for a directory level by level, using a queue instead of recursion
+///
+/// Every entry of a level is visited before any entry of the next one. Folders
+/// are assembled bottom-up once the traversal is done, so the result has the
+/// same shape and ordering as `build_structure`.
+///
+/// # Arguments
+/// * `path` - The directory to build the structure from
+/// * `options` - Configuration options for filtering
+/// * `state` - State shared across the traversal
+///
+/// # Returns
+/// * `FsResult<Item>` - The resulting item or an error
+fn build_structure_breadth_first(path: &Path, options: &FolderStructureOptions, state: &mut TraversalState) -> FsResult<Item> {
+    let mut folders = vec![PendingFolder { path: path.to_path_buf(), parent: None, depth: 0, items: Vec::new(), unreadable: None }];
+    let mut queue = VecDeque::from([0]);
+
+    while let Some(index) = queue.pop_front() {
+        if state.stopped {
+            break;
+        }
+
+        state.depth = folders[index].depth;
+        let folder_path = folders[index].path.clone();
+        if options.follow_symlinks {
+            let mut chain = Some(index);
+            state.ancestors.clear();
+            while let Some(ancestor) = chain {
+                state.ancestors.extend(folders[ancestor].path.canonicalize().ok());
+                chain = folders[ancestor].parent;
+            }
+            state.ancestors.reverse();
+        }
+        let entries = match read_dir_with_retry(&folder_path, options.read_retries, state.source) {
+            Ok(entries) => entries,
+            Err(e) if index > 0 && !options.strict => {
+                folders[index].unreadable = Some(e.kind());
+                continue;
+            }
+            Err(e) => return Err(FsError::at(e, &folder_path)),
+        };
+
+        for entry in entries {
+            if state.stopped {
+                break;
+            }
+
+            let path = match entry {
+                Ok(path) => path,
+                Err(_) if !options.strict => continue,
+                Err(e) => return Err(FsError::at(e, &folder_path)),
+            };
+            match classify_entry(&path, options, state) {
+                EntryAction::Skip => {}
+                EntryAction::Leaf(item) => folders[index].items.push(with_mode(item, &path, options)),
+                EntryAction::Descend if !is_traversable(&path, state.source) => {
+                    match handle_file(&path, get_path_name(&path), options, state) {
+                        Ok(item) => folders[index].items.push(mark_highlighted(item, &path, options)),
+                        Err(FsError::Filtered) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                EntryAction::Descend => {
+                    folders.push(PendingFolder { path, parent: Some(index), depth: state.depth + 1, items: Vec::new(), unreadable: None });
+                    queue.push_back(folders.len() - 1);
+                }
+            }
+        }
+    }
+
+    // Folders are queued after their parent, so popping from the end always
+    // completes a folder before the one containing it
+    while folders.len() > 1 {
+        let folder = folders.pop().expect("more than one folder is pending");
+        let parent = folder.parent.expect("only the root has no parent");
+        let path = folder.path.clone();
+        match finish_pending_folder(folder, options) {
+            Ok(item) => folders[parent].items.push(item),
+            Err(FsError::EmptyFolder) => {
+                skip_entry(&path, ExclusionReason::EmptyFolder, options, state);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let root = folders.pop().expect("the root folder is always pending");
+    finish_pending_folder(root, options)
+}
+
+/// A folder discovered by the breadth-first traversal whose item is not built yet
+struct PendingFolder {
+    /// Path to the folder
+    path: PathBuf,
+
+    /// Index of the containing folder, `None` for the root
+    parent: Option<usize>,
+
+    /// Depth of the folder's entries, 0 for the root's immediate children
+    depth: usize,
+
+    /// Entries collected so far, in directory order
+    items: Vec<Item>,
+
+    /// Why the folder could not be read, `None` if it was read
+    unreadable: Option<io::ErrorKind>,
+}
+
+/// Turns a pending folder into its folder item
+///
+/// # Arguments
+/// * `folder` - The folder with all its entries collected
+/// * `options` - Configuration options
+///
+/// # Returns
+/// * `FsResult<Item>` - The folder item or an error
+fn finish_pending_folder(folder: PendingFolder, options: &FolderStructureOptions) -> FsResult<Item> {
+    let item = match folder.unreadable {
+        Some(kind) => with_mode(create_marked_folder(get_path_name(&folder.path), unreadable_note(kind)), &folder.path, options),
+        None => create_folder_item(&folder.path, get_path_name(&folder.path), folder.items, options)?,
+    };
+    Ok(mark_highlighted(item, &folder.path, options))
+}
+
+/// Flags an item as the highlighted one if its path is the highlighted path
+///
+/// # Arguments
+/// * `item` - The item built for the path
+/// * `path` - Path the item was built from
+/// * `options` - Configuration options containing the highlighted path
+///
+/// # Returns
+/// * `Item` - The item, flagged if needed
+fn mark_highlighted(mut item: Item, path: &Path, options: &FolderStructureOptions) -> Item {
+    if options.highlight_path.as_deref() == Some(path) {
+        match &mut item {
+            Item::File(_, metadata) | Item::Folder(.., metadata) => metadata.highlighted = true,
+        }
+    }
+    item
+}
+
+/// Prints the complete folder structure as a tree
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+pub fn print_tree(root: &Item, option: &FolderStructureOptions) {
+    // Stdout is line-buffered, so lines are batched to avoid a flush per entry.
+    // Errors such as a closed pipe simply end the output early
+    let mut out = BufWriter::new(io::stdout().lock());
+    let _ = write_tree(root, option, &mut out).and_then(|_| out.flush());
+}
+
+/// Writes the complete folder structure as a tree
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+/// * `out` - The sink to write to
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+pub fn write_tree<W: Write>(root: &Item, option: &FolderStructureOptions, out: &mut W) -> io::Result<()> {
+    let column = match option.alignment {
+        Some(Alignment::Global) => Some(max_line_width(root, ".", 0, None, option)),
+        Some(Alignment::Width(width)) => Some(width),
+        Some(Alignment::PerDirectory) | None => None,
+    };
+    let depth_column = option.depth_column.then(|| {
+        let (max_depth, widest_path) = measure_depth_column(root, ".", 0, option);
+        let depth_width = max_depth.to_string().len().max(2);
+        (depth_width, option.depth_column_width.unwrap_or(widest_path))
+    });
+    let depth_marker = option.show_depth.then(|| measure_depth_column(root, ".", 0, option).0.to_string().len());
+
+    let markers = TreeMarkers::for_options(option);
+    let mut position = LinePosition {
+        prefix: "",
+        is_last: true,
+        depth: 0,
+        path: ".",
+        parent_size: None,
+        column,
+        depth_column,
+        depth_marker,
+        markers: &markers,
+    };
+    // The lead columns have the same width on every line
+    let lead_width = format_lead(Some(root), &position, option).chars().count();
+    position.column = column.map(|column| clamp_column(column, lead_width, max_annotation_width(root, None, option), option));
+    print_structure(out, root, &position, option)?;
+    write_summary(out, root, option)
+}
+
+/// Writes the `N directories, M files` line ending the tree output
+///
+/// Counts come from the built structure, so they reflect what was displayed.
+///
+/// # Arguments
+/// * `out` - The sink to write to
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+///
+/// # Returns
+/// * `io::Result<()>` - The error returned by the sink, if any
+fn write_summary<W: Write>(out: &mut W, root: &Item, option: &FolderStructureOptions) -> io::Result<()> {
+    if !option.show_summary || !is_displayed(root, option) {
+        return Ok(());
+    }
+
+    let summary = Summary::from_item(root, option);
+    let directories = if summary.dir_count == 1 { "directory" } else { "directories" };
+    if option.dirs_only {
+        return writeln!(out, "\n{} {}", summary.dir_count, directories);
+    }
+    let files = if summary.file_count == 1 { "file" } else { "files" };
+    writeln!(out, "\n{} {}, {} {}", summary.dir_count, directories, summary.file_count, files)
+}
+
+/// Renders the complete folder structure as a tree into a `String`
+///
+/// The whole output is buffered in memory, which is fine for most trees but
+/// means very large structures are better written with `write_tree`.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+///
+/// # Returns
+/// * `String` - The rendered tree
+pub fn render_tree(root: &Item, option: &FolderStructureOptions) -> String {
+    let mut buffer = Vec::new();
+    write_tree(root, option, &mut buffer).expect("writing to a Vec never fails");
+    String::from_utf8_lossy(&buffer).into_owned()
+}
+
+/// Label of the root line of a streamed tree, following `root_name` and `print_root_path`
+fn streamed_root_label(path: &Path, option: &FolderStructureOptions) -> String {
+    match &option.root_name {
+        Some(label) => label.clone(),
+        None if option.print_root_path => root_path_label(path),
+        None if path.as_os_str() == "." => ".".to_string(),
+        None => get_path_name(path),
+    }
+}
+
+/// Traverses a path with `walk` and writes each entry as a tree line as soon as it is found
+///
+/// Lines use the same connectors, labels, colors and file annotations as
+/// `write_tree`, and the summary counts what was written. Like `walk`, empty
+/// folders are listed and passes needing the whole tree are not applied; the
+/// columns left of the tree, alignment, `min_depth` and `max_entries` are
+/// ignored too.
+///
+/// # Arguments
+/// * `path` - The starting path to traverse
+/// * `option` - Configuration options for filtering and display
+/// * `out` - The sink to write to
+///
+/// # Returns
+/// * `Result<StreamSummary, StreamError>` - What was written, or the error that stopped the
+///   output. A failing sink stops the traversal at once
+pub fn write_streamed<W: Write>(path: &Path, option: &FolderStructureOptions, out: &mut W) -> Result<StreamSummary, StreamError> {
+    let markers = TreeMarkers::for_options(option);
+    // Prefix of the entries of each folder being listed, the innermost last
+    let mut prefixes: Vec<String> = Vec::new();
+    let (mut dir_count, mut file_count) = (0, 0);
+    let mut written = Ok(());
+
+    let entry_limit_reached = walk(path, option, |event| {
+        let (item, entry_path, depth, is_last, entered) = match event {
+            WalkEvent::EnterDir { path: entry_path, depth, is_last } => {
+                let metadata = ItemMetadata { symlink: is_symlink(entry_path), ..ItemMetadata::default() };
+                (Item::Folder(get_path_name(entry_path), Vec::new(), None, metadata), entry_path, depth, is_last, true)
+            }
+            WalkEvent::File { path: entry_path, item, depth, is_last } => (item, entry_path, depth, is_last, false),
+            WalkEvent::LeaveDir { .. } => {
+                prefixes.pop();
+                return ControlFlow::Continue(());
+            }
+        };
+        let is_folder = matches!(item, Item::Folder(..));
+
+        let line = if depth == 0 {
+            write_line(out, "", "", &streamed_root_label(path, option), name_style(&item, option), "", None)
+        } else {
+            let prefix = prefixes.last().map_or("", String::as_str);
+            let connector = format!("{}{}", prefix, markers.connector(is_last));
+            let relative = entry_path.strip_prefix(path).unwrap_or(entry_path).to_string_lossy().replace('\\', "/");
+            let label = entry_label(&item, &relative, depth, option);
+            let annotation = if entered { String::new() } else { format_annotations(&item, None, option) };
+            write_line(out, "", &connector, &label, name_style(&item, option), &annotation, None)
+        };
+        if let Err(error) = line {
+            written = Err(error);
+            return ControlFlow::Break(());
+        }
+
+        if entered {
+            // The children of the root line are indented by a blank prefix, as in `write_tree`
+            let next = if is_last { &markers.blank } else { &markers.vertical };
+            let prefix = match prefixes.last() {
+                Some(prefix) => format!("{}{}", prefix, next),
+                None => markers.blank.clone(),
+            };
+            prefixes.push(prefix);
+        }
+        match (is_folder, depth) {
+            (_, 0) => {}
+            (true, _) => dir_count += 1,
+            (false, _) => file_count += 1,
+        }
+        ControlFlow::Continue(())
+    })?;
+    written.map_err(StreamError::Write)?;
+
+    if option.show_summary {
+        let directories = if dir_count == 1 { "directory" } else { "directories" };
+        let summary = if option.dirs_only {
+            writeln!(out, "\n{} {}", dir_count, directories)
+        } else {
+            let files = if file_count == 1 { "file" } else { "files" };
+            writeln!(out, "\n{} {}, {} {}", dir_count, directories, file_count, files)
+        };
+        summary.map_err(StreamError::Write)?;
+    }
+    Ok(StreamSummary { files: file_count, entry_limit_reached })
+}
+
+/// Prints the folder structure level by level
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+pub fn print_breadth_first(root: &Item, option: &FolderStructureOptions) {
+    // Stdout errors such as a closed pipe simply end the output early
+    let mut out = BufWriter::new(io::stdout().lock());
+    let _ = write_breadth_first(root, option, &mut out).and_then(|_| out.flush());
+}
+
+/// Renders the folder structure level by level into a `String`
+///
+/// The whole output is buffered in memory.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+///
+/// # Returns
+/// * `String` - The rendered lines
+pub fn render_breadth_first(root: &Item, option: &FolderStructureOptions) -> String {
+    let mut buffer = Vec::new();
+    write_breadth_first(root, option, &mut buffer).expect("writing to a Vec never fails");
+    String::from_utf8_lossy(&buffer).into_owned()
+}
+
+/// Writes the path of every displayed file, one per line, without any tree drawing
+///
+/// Paths are relative to the root and come in the tree's pre-order. With
+/// `dirs_only` the structure holds no file, so folders are written instead,
+/// each ending with `/`. No summary line is written, so the output can be piped.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+/// * `out` - The sink to write to
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+pub fn write_flat<W: Write>(root: &Item, option: &FolderStructureOptions, out: &mut W) -> io::Result<()> {
+    if !is_displayed(root, option) {
+        return Ok(());
+    }
+    match root {
+        Item::File(name, _) => writeln!(out, "{}{}", name, format_annotations(root, None, option)),
+        Item::Folder(_, items, _, metadata) => write_flat_entries(items, "", metadata.size, option, out),
+    }
+}
+
+/// Writes the flat lines of a folder's displayed children and their descendants
+///
+/// # Arguments
+/// * `items` - The children of the folder
+/// * `parent` - Relative path of the folder with a trailing `/`, empty for the root
+/// * `parent_size` - Total size of the folder
+/// * `option` - Configuration options for display
+/// * `out` - The sink to write to
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+fn write_flat_entries<W: Write>(items: &[Item], parent: &str, parent_size: Option<u64>, option: &FolderStructureOptions, out: &mut W) -> io::Result<()> {
+    for item in items.iter().filter(|item| is_displayed(item, option)) {
+        let path = format!("{}{}", parent, item.name());
+        let annotation = format_annotations(item, parent_size, option);
+        match item {
+            Item::File(..) => writeln!(out, "{}{}", path, annotation)?,
+            Item::Folder(_, children, _, metadata) => {
+                if option.dirs_only {
+                    writeln!(out, "{}/{}", path, annotation)?;
+                }
+                write_flat_entries(children, &format!("{}/", path), metadata.size, option, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the folder structure level by level
+///
+/// All depth-1 entries are written first, then all depth-2 entries, and so on.
+/// Each line holds the entry's path relative to the root, and entries keep
+/// their sorted order within each level.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `option` - Configuration options for display
+/// * `out` - The sink to write to
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+pub fn write_breadth_first<W: Write>(root: &Item, option: &FolderStructureOptions, out: &mut W) -> io::Result<()> {
+    if !is_displayed(root, option) {
+        return Ok(());
+    }
+    if option.min_depth == 0 {
+        let du = format_du_column(Some(root.metadata().size), option);
+        let perms = format_perms_column(Some(root.metadata().mode), option);
+        let mtime = format_mtime_column(Some(root.metadata().modified), option);
+        writeln!(out, "{}{}{}{}{}", du, perms, mtime, root.name(), format_annotations(root, None, option))?;
+    }
+
+    let mut queue = VecDeque::new();
+    queue.push_back((root, String::new(), 0));
+
+    while let Some((item, path, depth)) = queue.pop_front() {
+        let Item::Folder(_, items, _, metadata) = item else {
+            continue;
+        };
+
+        let shown = depth + 1 >= option.min_depth;
+        for child in items.iter().filter(|child| is_displayed(child, option)) {
+            let child_path = format!("{}{}", path, child.name());
+            let annotation = format_annotations(child, metadata.size, option);
+            let du = format_du_column(Some(child.metadata().size), option);
+            let perms = format_perms_column(Some(child.metadata().mode), option);
+            let mtime = format_mtime_column(Some(child.metadata().modified), option);
+            let lead = format!("{}{}{}", du, perms, mtime);
+            match child {
+                Item::File(..) if shown => writeln!(out, "{}{}{}", lead, child_path, annotation)?,
+                Item::File(..) => {}
+                Item::Folder(..) => {
+                    if shown {
+                        writeln!(out, "{}{}/{}", lead, child_path, annotation)?;
+                    }
+                    queue.push_back((child, format!("{}/", child_path), depth + 1));
+                }
+            }
+        }
+    }
+
+    write_summary(out, root, option)
+}
+
+/// Determines if a file should be included based on its name length and extension filters
+///
+/// The name length is its number of characters, so a multibyte name such as
+/// `日本.txt` counts 6.
+///
+/// # Arguments
+/// * `file_name` - Name of the file to check
+/// * `options` - Filter options containing include/exclude patterns
+///
+/// # Returns
+/// * `Result<InclusionReason, ExclusionReason>` - Why the file is included or filtered out
+fn should_include_file(file_name: &str, options: &FolderStructureOptions) -> Result<InclusionReason, ExclusionReason> {
+    let length = file_name.chars().count();
+    if options.min_name_length.is_some_and(|min| length < min) || options.max_name_length.is_some_and(|max| length > max) {
+        return Err(ExclusionReason::NameLength);
+    }
+
+    // If both vectors are empty, include all files
+    if options.exclude_extension.is_empty() && options.include_extension_only.is_empty() {
+        return Ok(InclusionReason::NoFilters);
+    }
+
+    // The include list applies first, so an empty one keeps every file
+    let reason = if options.include_extension_only.is_empty() {
+        InclusionReason::NotExcludedExtension
+    } else {
+        let included = options.include_extension_only.iter()
+            .find(|ext| extension_matches(file_name, ext))
+            .ok_or(ExclusionReason::NotIncludedExtension)?;
+        InclusionReason::IncludedExtension(included.clone())
+    };
+
+    // The exclude list then subtracts from what was included
+    match options.exclude_extension.iter().find(|ext| extension_matches(file_name, ext)) {
+        Some(excluded) => Err(ExclusionReason::ExcludedExtension(excluded.clone())),
+        None => Ok(reason),
+    }
+}
+
+/// Compares a file's extension against a filter entry
+///
+/// The filter entry may be given with or without its leading dot, and the
+/// comparison is case-insensitive. An entry spanning several dots, such as
+/// `generated.rs`, matches the end of the name. Files without an extension
+/// (including dotfiles like `.bashrc`) never match.
+///
+/// # Arguments
+/// * `file_name` - Name of the file
+/// * `filter` - Extension from the include or exclude list, e.g. `rs`, `.RS` or `generated.rs`
+///
+/// # Returns
+/// * `bool` - True if the file has the filtered extension
+fn extension_matches(file_name: &str, filter: &str) -> bool {
+    let filter = filter.trim_start_matches('.');
+    if !filter.contains('.') {
+        return Path::new(file_name).extension()
+            .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case(filter));
+    }
+
+    // The suffix must follow a non-empty stem, as a single extension would
+    let Some(stem_end) = file_name.len().checked_sub(filter.len() + 1) else {
+        return false;
+    };
+    stem_end > 0
+        && file_name.is_char_boundary(stem_end)
+        && file_name[stem_end..].starts_with('.')
+        && file_name[stem_end + 1..].eq_ignore_ascii_case(filter)
+}
+
+/// Determines if an item should be included based on name filters
+///
+/// Include patterns are checked first and only apply to files; an item matching
+/// an exclude pattern is always filtered out.
+///
+/// # Arguments
+/// * `item_name` - Name of the item to check
+/// * `is_folder` - Whether the item is a folder
+/// * `options` - Filter options containing regex patterns
+///
+/// # Returns
+/// * `Result<InclusionReason, ExclusionReason>` - Why the item is included or filtered out
+fn should_include_item(item_name: &str, is_folder: bool, options: &FolderStructureOptions) -> Result<InclusionReason, ExclusionReason> {
+    let included = if is_folder || options.include_by_filter.is_empty() {
+        None
+    } else {
+        // Keep only files matching at least one include pattern
+        let index = options.include_by_filter.matches(item_name).into_iter().next()
+            .ok_or(ExclusionReason::NoIncludedPattern)?;
+        Some(InclusionReason::IncludedPattern(options.include_by_filter.patterns()[index].clone()))
+    };
+
+    // Ignore file patterns stand on their own, so `filter_mode` never combines them with the others
+    if let Some(index) = options.ignore_patterns.matches(item_name).into_iter().next() {
+        return Err(ExclusionReason::ExcludedPattern(options.ignore_patterns.patterns()[index].clone()));
+    }
+
+    let matches = options.exclude_by_filter.matches(item_name);
+    match options.filter_mode {
+        FilterMode::Any => {
+            if let Some(index) = matches.into_iter().next() {
+                return Err(ExclusionReason::ExcludedPattern(options.exclude_by_filter.patterns()[index].clone()));
+            }
+        }
+        FilterMode::All if matches.matched_all() && !options.exclude_by_filter.is_empty() => {
+            return Err(ExclusionReason::AllPatternsMatched);
+        }
+        FilterMode::All => {}
+    }
+
+    match included {
+        Some(reason) => Ok(reason),
+        None if options.exclude_by_filter.is_empty() && options.ignore_patterns.is_empty() => Ok(InclusionReason::NoFilters),
+        None if options.filter_mode == FilterMode::All && !options.exclude_by_filter.is_empty() => {
+            Ok(InclusionReason::NotAllPatternsMatched)
+        }
+        None => Ok(InclusionReason::NoPatternMatched),
+    }
+}
+
+/// Joins inclusion reasons for display, dropping the filters that are not active
+///
+/// # Arguments
+/// * `reasons` - The reasons collected for an item
+///
+/// # Returns
+/// * `String` - The reasons separated by commas, or `no filters` when none is active
+fn format_reasons(reasons: &[InclusionReason]) -> String {
+    let active = reasons.iter()
+        .filter(|reason| **reason != InclusionReason::NoFilters)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    if active.is_empty() {
+        InclusionReason::NoFilters.to_string()
+    } else {
+        active.join(", ")
+    }
+}
+
+/// Determines if a file's stem equals the name of its parent folder, like `foo/foo.rs`
+///
+/// # Arguments
+/// * `path` - Path to the file
+/// * `ignore_case` - Whether to compare case-insensitively
+///
+/// # Returns
+/// * `bool` - True if the stem matches the parent folder's name
+fn matches_parent_name(path: &Path, ignore_case: bool) -> bool {
+    let Some(stem) = path.file_stem().map(|s| s.to_string_lossy()) else {
+        return false;
+    };
+
+    // `.` and similar parents have no name of their own, so resolve them first
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent_name = match parent.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => match parent.canonicalize() {
+            Ok(resolved) => get_path_name(&resolved),
+            Err(_) => return false,
+        },
+    };
+
+    if ignore_case {
+        stem.to_lowercase() == parent_name.to_lowercase()
+    } else {
+        stem == parent_name
+    }
+}
+
+/// Determines if a file passes the ownership filters
+///
+/// Files whose metadata cannot be read never match an active ownership filter.
+///
+/// # Arguments
+/// * `metadata` - Metadata of the file, if it could be read
+/// * `options` - Filter options containing the owner uids
+///
+/// # Returns
+/// * `bool` - True if the file should be included
+#[cfg(unix)]
+fn matches_owner(metadata: Option<&EntryMetadata>, options: &FolderStructureOptions) -> bool {
+    if options.owner_uid.is_none() && options.not_owner_uid.is_none() {
+        return true;
+    }
+
+    match metadata.and_then(|m| m.uid) {
+        Some(uid) => options.owner_uid.is_none_or(|owner| owner == uid) && options.not_owner_uid != Some(uid),
+        None => false,
+    }
+}
+
+/// Determines if a file passes the size bounds
+///
+/// Files whose metadata cannot be read never match an active size filter.
+///
+/// # Arguments
+/// * `metadata` - Metadata of the file, if it could be read
+/// * `options` - Filter options containing the size bounds
+///
+/// # Returns
+/// * `bool` - True if the file should be included
+fn matches_size(metadata: Option<&EntryMetadata>, options: &FolderStructureOptions) -> bool {
+    if options.min_size.is_none() && options.max_size.is_none() {
+        return true;
+    }
+
+    match metadata.map(|m| m.size) {
+        Some(size) => options.min_size.is_none_or(|min| size >= min) && options.max_size.is_none_or(|max| size <= max),
+        None => false,
+    }
+}
+
+/// Determines if a file passes the modification time bounds
+///
+/// Files whose modification time cannot be read never match an active age filter.
+///
+/// # Arguments
+/// * `metadata` - Metadata of the file, if it could be read
+/// * `options` - Filter options containing the age bounds
+///
+/// # Returns
+/// * `bool` - True if the file should be included
+fn matches_age(metadata: Option<&EntryMetadata>, options: &FolderStructureOptions) -> bool {
+    if options.newer_than.is_none() && options.older_than.is_none() {
+        return true;
+    }
+
+    let Some(modified) = metadata.and_then(|m| m.modified) else {
+        return false;
+    };
+    let now = SystemTime::now();
+    let threshold = |window: Duration| now.checked_sub(window).unwrap_or(SystemTime::UNIX_EPOCH);
+
+    options.newer_than.is_none_or(|window| modified >= threshold(window))
+        && options.older_than.is_none_or(|window| modified < threshold(window))
+}
+
+/// Checks whether a path is itself a symbolic link, without following it
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Reads where a symlink points, as stored in the link, empty if it cannot be read
+fn link_target(path: &Path) -> String {
+    fs::read_link(path)
+        .map(|target| target.display().to_string())
+        .unwrap_or_default()
+}
+
+/// Ownership filters are a no-op outside Unix
+#[cfg(not(unix))]
+fn matches_owner(_metadata: Option<&EntryMetadata>, _options: &FolderStructureOptions) -> bool {
+    true
+}
+
+/// Updates the has_terminal_file flag for all folders in the structure
+///
+/// # Arguments
+/// * `item` - The item to update
+///
+/// # Returns
+/// * `bool` - True if this item or any of its children contain a terminal file
+fn update_has_terminal_file(item: &mut Item) -> bool {
+    match item {
+        Item::File(..) => true,
+        Item::Folder(_, items, has_terminal, metadata) => {
+            // Visit every child so nested folders all get their flag set.
+            // A folder whose children were not traversed is kept visible as a leaf
+            let mut contains_terminal = metadata.truncated;
+            for item in items.iter_mut() {
+                contains_terminal |= update_has_terminal_file(item);
+            }
+            *has_terminal = Some(contains_terminal);
+            contains_terminal
+        }
+    }
+}
+
+/// Flags the folders matching `keep_dirs`, their descendants and their ancestors as displayed
+///
+/// Runs after `update_has_terminal_file` and overrides its flag, so kept folders
+/// are shown with their whole subtree and stay reachable from the root.
+///
+/// # Arguments
+/// * `item` - The item to update
+/// * `inside_kept` - Whether an ancestor of the item is a kept folder
+/// * `options` - Configuration options holding the patterns
+///
+/// # Returns
+/// * `bool` - True if the item is or contains a kept folder, or lies inside one
+fn mark_kept_dirs(item: &mut Item, inside_kept: bool, options: &FolderStructureOptions) -> bool {
+    let Item::Folder(name, items, has_terminal, _) = item else {
+        return false;
+    };
+
+    let kept = inside_kept || options.keep_dirs.is_match(name);
+    let mut displayed = kept;
+    for child in items.iter_mut() {
+        displayed |= mark_kept_dirs(child, kept, options);
+    }
+    if displayed {
+        *has_terminal = Some(true);
+    }
+    displayed
+}
+
+/// Propagates the most recent file modification time up to every folder
+///
+/// # Arguments
+/// * `item` - The item to update
+///
+/// # Returns
+/// * `Option<SystemTime>` - The most recent modification time found in this item
+fn update_latest_modified(item: &mut Item) -> Option<SystemTime> {
+    match item {
+        Item::File(_, metadata) => metadata.modified,
+        Item::Folder(_, items, _, metadata) => {
+            metadata.modified = items.iter_mut()
+                .filter_map(update_latest_modified)
+                .max();
+            metadata.modified
+        }
+    }
+}
+
+/// Sums file sizes up to every folder
+///
+/// Files whose size could not be read count as zero. Folders listed without
+/// their contents have an unknown size.
+///
+/// # Arguments
+/// * `item` - The item to update
+///
+/// # Returns
+/// * `u64` - The total size of this item
+fn update_total_size(item: &mut Item) -> u64 {
+    match item {
+        Item::File(_, metadata) => metadata.size.unwrap_or(0),
+        Item::Folder(_, _, _, metadata) if metadata.truncated => 0,
+        Item::Folder(_, items, _, metadata) => {
+            let total = items.iter_mut()
+                .map(update_total_size)
+                .fold(0u64, u64::saturating_add);
+            metadata.size = Some(total);
+            total
+        }
+    }
+}
+
+/// Removes every file from the structure, keeping all folders
+///
+/// Runs after the terminal flags and sizes are computed, so folders that held
+/// matching files stay displayed and keep their total size.
+///
+/// # Arguments
+/// * `item` - The item to prune in place
+fn remove_files(item: &mut Item) {
+    if let Item::Folder(_, items, ..) = item {
+        items.retain(|child| matches!(child, Item::Folder(..)));
+        items.iter_mut().for_each(remove_files);
+    }
+}
+
+/// Keeps only the folders containing a file modified since the threshold, dropping all files
+///
+/// # Arguments
+/// * `items` - The items to prune in place
+/// * `threshold` - The oldest modification time still considered active
+fn retain_active_dirs(items: &mut Vec<Item>, threshold: SystemTime) {
+    items.retain_mut(|item| match item {
+        Item::File(..) => false,
+        Item::Folder(_, children, _, metadata) => {
+            let is_active = metadata.modified.is_some_and(|modified| modified >= threshold);
+            if is_active {
+                retain_active_dirs(children, threshold);
+            }
+            is_active
+        }
+    });
+}
+
+/// Keeps only the folders with at least `min_children` immediate children and their ancestors
+///
+/// Files are dropped, and every flagged folder is annotated with its child count.
+///
+/// # Arguments
+/// * `item` - The item to prune in place
+/// * `min_children` - The minimum number of immediate children for a folder to be flagged
+///
+/// # Returns
+/// * `bool` - True if the item is flagged or has a flagged descendant
+fn retain_crowded_dirs(item: &mut Item, min_children: usize) -> bool {
+    let Item::Folder(_, items, has_terminal, metadata) = item else {
+        return false;
+    };
+
+    let child_count = items.len();
+    items.retain_mut(|child| retain_crowded_dirs(child, min_children));
+
+    let is_flagged = child_count >= min_children;
+    if is_flagged {
+        metadata.note = Some(format!("{} children", child_count));
+    }
+
+    let keep = is_flagged || !items.is_empty();
+    if keep {
+        *has_terminal = Some(true);
+    }
+    keep
+}
+
+/// Keeps only the entries matching a target path and the folders leading to them
+///
+/// An entry matches when its path relative to the root ends with the target
+/// components, so `main.rs` matches every `main.rs` while `src/main.rs` is more specific.
+/// Matched folders keep their whole subtree.
+///
+/// # Arguments
+/// * `items` - The items to prune in place
+/// * `ancestors` - Names of the folders leading to `items`
+/// * `target` - Components of the target path
+/// * `keep_siblings` - Whether to keep the siblings of matched entries, listed without their contents
+///
+/// # Returns
+/// * `bool` - True if any entry below `items` matched
+fn retain_target_spines(items: &mut Vec<Item>, ancestors: &mut Vec<String>, target: &[String], keep_siblings: bool) -> bool {
+    let mut matched = vec![false; items.len()];
+    let mut on_spine = vec![false; items.len()];
+
+    for (index, item) in items.iter_mut().enumerate() {
+        ancestors.push(item.name().to_string());
+        matched[index] = ancestors.ends_with(target);
+        if !matched[index] {
+            if let Item::Folder(_, children, ..) = item {
+                on_spine[index] = retain_target_spines(children, ancestors, target, keep_siblings);
+            }
+        }
+        ancestors.pop();
+    }
+
+    let any_matched = matched.iter().any(|m| *m);
+    let mut index = 0;
+    items.retain_mut(|item| {
+        let keep = matched[index] || on_spine[index];
+        let is_sibling = keep_siblings && any_matched && !keep;
+        index += 1;
+
+        if is_sibling {
+            if let Item::Folder(_, children, _, metadata) = item {
+                children.clear();
+                metadata.truncated = true;
+            }
+        }
+        keep || is_sibling
+    });
+
+    any_matched || on_spine.iter().any(|s| *s)
+}
+
+/// Extracts the name from a path
+///
+/// # Arguments
+/// * `path` - The path to extract the name from
+///
+/// # Returns
+/// * `String` - The extracted name
+fn get_path_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Labels the root with its canonical absolute path
+///
+/// # Arguments
+/// * `path` - The traversal root as given
+///
+/// # Returns
+/// * `String` - The canonical path, or the given path if it cannot be canonicalized
+fn root_path_label(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// Processes a file item
+///
+/// # Arguments
+/// * `path` - Path to the file
+/// * `name` - Name of the file
+/// * `options` - Configuration options for filtering
+/// * `state` - State shared across the traversal
+///
+/// # Returns
+/// * `FsResult<Item>` - The file item or a filtered error
+fn handle_file(path: &Path, name: String, options: &FolderStructureOptions, state: &mut TraversalState) -> FsResult<Item> {
+    let extension_reason = match should_include_file(&name, options) {
+        Ok(reason) => reason,
+        Err(reason) => return Err(skip_entry(path, reason, options, state)),
+    };
+
+    if options.mod_files_only && !matches_parent_name(path, options.mod_files_ignore_case || options.ignore_case) {
+        return Err(skip_entry(path, ExclusionReason::NotModFile, options, state));
+    }
+
+    let fs_metadata = state.source.metadata(path);
+    if !matches_owner(fs_metadata.as_ref(), options) {
+        return Err(skip_entry(path, ExclusionReason::Owner, options, state));
+    }
+
+    if !matches_size(fs_metadata.as_ref(), options) {
+        return Err(skip_entry(path, ExclusionReason::Size, options, state));
+    }
+
+    if !matches_age(fs_metadata.as_ref(), options) {
+        return Err(skip_entry(path, ExclusionReason::Age, options, state));
+    }
+
+    // Only regular files are opened, reading a pipe or device could block
+    let mismatch = ((options.check_ext_mismatch || options.ext_mismatch_only) && fs_metadata.as_ref().is_some_and(|m| m.is_file))
+        .then(|| detect_extension_mismatch(path))
+        .flatten();
+    if options.ext_mismatch_only && mismatch.is_none() {
+        return Err(skip_entry(path, ExclusionReason::NoExtMismatch, options, state));
+    }
+
+    let symlink = is_symlink(path);
+    let inodes = fs_metadata.as_ref().filter(|_| options.show_inodes);
+    let mut metadata = ItemMetadata {
+        inode: inodes.and_then(|m| m.inode),
+        hard_links: inodes.and_then(|m| m.hard_links),
+        mode: fs_metadata.as_ref().filter(|_| options.show_perms).and_then(|m| m.mode),
+        modified: fs_metadata.as_ref().and_then(|m| m.modified),
+        size: fs_metadata.as_ref().map(|m| m.size),
+        symlink,
+        link: symlink.then(|| link_target(path)),
+        executable: fs_metadata.as_ref().and_then(|m| m.mode).is_some_and(|mode| mode & 0o111 != 0),
+        note: mismatch.map(|mismatch| format!("ext mismatch: declared {}, detected {}", mismatch.declared, mismatch.detected)),
+        ..ItemMetadata::default()
+    };
+
+    if options.explain {
+        let mut reasons = vec![extension_reason];
+        reasons.extend(should_include_item(&name, false, options));
+        if options.owner_uid.is_some() || options.not_owner_uid.is_some() {
+            reasons.push(InclusionReason::OwnerMatched);
+        }
+        if options.min_size.is_some() || options.max_size.is_some() {
+            reasons.push(InclusionReason::SizeMatched);
+        }
+        if options.min_name_length.is_some() || options.max_name_length.is_some() {
+            reasons.push(InclusionReason::NameLengthMatched);
+        }
+        if options.newer_than.is_some() || options.older_than.is_some() {
+            reasons.push(InclusionReason::AgeMatched);
+        }
+        if options.mod_files_only {
+            reasons.push(InclusionReason::ModFile);
+        }
+        if options.ext_mismatch_only {
+            reasons.push(InclusionReason::ExtMismatch);
+        }
+        metadata.explanation = Some(format_reasons(&reasons));
+    }
+
+    if let Some(budget) = options.stop_at_size {
+        state.collected_size = state.collected_size.saturating_add(metadata.size.unwrap_or(0));
+        state.stopped = state.collected_size >= budget;
+    }
+    if options.first_only {
+        state.stopped = true;
+    }
+
+    Ok(Item::File(name, metadata))
+}
+
+/// Processes a directory and its contents
+///
+/// # Arguments
+/// * `path` - Path to the directory
+/// * `options` - Configuration options for filtering
+/// * `state` - State shared across the traversal
+///
+/// # Returns
+/// * `FsResult<Vec<Item>>` - Vector of processed items or an error
+fn process_directory(path: &Path, options: &FolderStructureOptions, state: &mut TraversalState) -> FsResult<Vec<Item>> {
+    let mut items = Vec::new();
+    let mut subfolders = Vec::new();
+    let parallel = options.jobs > 1 && state.depth == 0 && options.stop_at_size.is_none() && !options.first_only
+        && options.max_total_entries.is_none();
+
+    let entries = read_dir_with_retry(path, options.read_retries, state.source).map_err(|e| FsError::at(e, path))?;
+    for entry in entries {
+        if state.stopped {
+            break;
+        }
+
+        let path = match entry {
+            Ok(path) => path,
+            Err(_) if !options.strict => continue,
+            Err(e) => return Err(FsError::at(e, path)),
+        };
+
+        match classify_entry(&path, options, state) {
+            EntryAction::Skip => continue,
+            EntryAction::Leaf(item) => {
+                items.push(with_mode(item, &path, options));
+                continue;
+            }
+            EntryAction::Descend if parallel && is_traversable(&path, state.source) => {
+                subfolders.push(path);
+                continue;
+            }
+            EntryAction::Descend => {}
+        }
+
+        state.depth += 1;
+        let result = build_child(&path, options, state);
+        state.depth -= 1;
+
+        match result {
+            Ok(item) => items.push(item),
+            Err(FsError::Filtered) | Err(FsError::EmptyFolder) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    if !subfolders.is_empty() {
+        items.extend(build_in_parallel(&subfolders, options, state)?);
+    }
+
+    Ok(items)
+}
+
+/// Builds sibling folders concurrently on up to `jobs` threads
+///
+/// Each worker takes the next unbuilt folder until none is left, with its own
+/// traversal state seeded from the parent's.
+///
+/// # Arguments
+/// * `paths` - Paths to the sibling folders
+/// * `options` - Configuration options for filtering
+/// * `state` - State of the traversal at the folders' parent
+///
+/// # Returns
+/// * `FsResult<Vec<Item>>` - The built folders, unsorted, or the first error
+fn build_in_parallel(paths: &[PathBuf], options: &FolderStructureOptions, state: &TraversalState) -> FsResult<Vec<Item>> {
+    let next = AtomicUsize::new(0);
+    let workers = options.jobs.min(paths.len());
+
+    let results = thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| scope.spawn(|| {
+                let mut worker_state = TraversalState {
+                    depth: state.depth + 1,
+                    ancestors: state.ancestors.clone(),
+                    scanned: Arc::clone(&state.scanned),
+                    ..TraversalState::new(&state.root, state.source)
+                };
+                let mut built = Vec::new();
+                while let Some(path) = paths.get(next.fetch_add(1, atomic::Ordering::Relaxed)) {
+                    built.push(build_child(path, options, &mut worker_state));
+                }
+                built
+            }))
+            .collect::<Vec<_>>();
+
+        handles.into_iter()
+            .flat_map(|handle| handle.join().expect("traversal worker panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut items = Vec::new();
+    for result in results {
+        match result {
+            Ok(item) => items.push(item),
+            Err(FsError::Filtered) | Err(FsError::EmptyFolder) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(items)
+}
+
+/// What the traversal does with a directory entry
+#[allow(clippy::large_enum_variant)] // Consumed right away, boxing would allocate per leaf
+enum EntryAction {
+    /// The entry is filtered out
+    Skip,
+
+    /// The entry is listed as-is, without being traversed
+    Leaf(Item),
+
+    /// The entry is built like any other file or folder
+    Descend,
+}
+
+/// Decides how a directory entry is handled before it is built
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `options` - Configuration options for filtering
+/// * `state` - State shared across the traversal
+///
+/// # Returns
+/// * `EntryAction` - Whether to skip, list or build the entry
+fn classify_entry(path: &Path, options: &FolderStructureOptions, state: &mut TraversalState) -> EntryAction {
+    let scanned = state.scanned.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+    if let Some(on_progress) = options.on_progress.as_ref().filter(|_| scanned.is_multiple_of(PROGRESS_INTERVAL)) {
+        on_progress(Progress::Scanned(scanned));
+    }
+
+    if options.max_total_entries.is_some_and(|max| scanned > max) {
+        state.stopped = true;
+        return EntryAction::Skip;
+    }
+
+    let source = state.source;
+    if let Some(reason) = should_skip_entry(path, options, source) {
+        skip_entry(path, reason, options, state);
+        return EntryAction::Skip;
+    }
+
+    if options.respect_gitignore && is_gitignored(path, state) {
+        skip_entry(path, ExclusionReason::Gitignored, options, state);
+        return EntryAction::Skip;
+    }
+
+    if !matches_globs(path, options, state) {
+        skip_entry(path, ExclusionReason::Glob, options, state);
+        return EntryAction::Skip;
+    }
+
+    if let Some(pattern) = excluding_path_pattern(path, options, state) {
+        skip_entry(path, ExclusionReason::ExcludedPath(pattern), options, state);
+        return EntryAction::Skip;
+    }
+
+    if options.skip_reparse && source.is_dir(path) && is_reparse_point(path) {
+        return EntryAction::Leaf(create_marked_folder(get_path_name(path), "reparse"));
+    }
+
+    if let Some(marker) = find_skip_marker(path, options, source) {
+        return EntryAction::Leaf(create_marked_folder(get_path_name(path), &format!("skipped: {}", marker)));
+    }
+
+    if is_collapsed(path, options, source) {
+        let note = match source.read_dir(path) {
+            Ok(entries) => match entries.len() {
+                1 => "1 item".to_string(),
+                count => format!("{} items", count),
+            },
+            Err(error) => unreadable_note(error.kind()).to_string(),
+        };
+        return EntryAction::Leaf(create_marked_folder(get_path_name(path), &note));
+    }
+
+    if source.is_dir(path) && is_symlink(path) && !options.follow_symlinks {
+        return EntryAction::Leaf(create_link_folder(get_path_name(path), link_target(path)));
+    }
+
+    if options.max_depth.is_some_and(|max_depth| state.depth >= max_depth) && source.is_dir(path) {
+        // Keep non-empty folders visible even though their children are not listed
+        if options.show_empty_folder || has_entries(path, options.show_hidden, source) {
+            return EntryAction::Leaf(create_truncated_folder(get_path_name(path)));
+        }
+        skip_entry(path, ExclusionReason::EmptyFolder, options, state);
+        return EntryAction::Skip;
+    }
+
+    // Only the folders being traversed are tracked, so a link to a folder listed
+    // elsewhere is followed and the result does not depend on the directory order
+    if options.follow_symlinks && source.is_dir(path) && is_symlink(path)
+        && path.canonicalize().is_ok_and(|canonical| state.ancestors.contains(&canonical))
+    {
+        return EntryAction::Leaf(create_link_folder(get_path_name(path), "(cycle)".to_string()));
+    }
+
+    EntryAction::Descend
+}
+
+/// Checks an entry against the include and exclude globs
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `options` - Configuration options containing the globs
+/// * `state` - State holding the traversal root the globs are anchored at
+///
+/// # Returns
+/// * `bool` - True if the entry should be kept
+fn matches_globs(path: &Path, options: &FolderStructureOptions, state: &TraversalState) -> bool {
+    if options.include_globs.is_empty() && options.exclude_globs.is_empty() {
+        return true;
+    }
+
+    let Some(relative) = relative_path(path, &state.root) else {
+        return true;
+    };
+
+    if options.exclude_globs.is_match(&relative) {
+        return false;
+    }
+
+    // Folders are kept so matching files below them can be reached
+    options.include_globs.is_empty() || state.source.is_dir(path) || options.include_globs.is_match(&relative)
+}
+
+/// Checks an entry's path relative to the traversal root against the path exclude patterns
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `options` - Configuration options containing the patterns
+/// * `state` - State holding the traversal root the paths are relative to
+///
+/// # Returns
+/// * `Option<String>` - The first matching pattern, `None` if the entry is kept
+fn excluding_path_pattern(path: &Path, options: &FolderStructureOptions, state: &TraversalState) -> Option<String> {
+    if options.exclude_by_path.is_empty() {
+        return None;
+    }
+
+    let relative = relative_path(path, &state.root)?;
+    let index = options.exclude_by_path.matches(&relative).into_iter().next()?;
+    Some(options.exclude_by_path.patterns()[index].clone())
+}
+
+/// Reports an entry left out of the structure to the `on_skip` callback, if any
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `reason` - Why the entry is left out
+/// * `options` - Configuration options deciding whether to report
+/// * `state` - State holding the traversal root the reported path is relative to
+///
+/// # Returns
+/// * `FsError` - `FsError::Filtered`, for callers returning the skip as an error
+fn skip_entry(path: &Path, reason: ExclusionReason, options: &FolderStructureOptions, state: &TraversalState) -> FsError {
+    if let Some(on_skip) = &options.on_skip {
+        let shown = relative_path(path, &state.root)
+            .filter(|relative| !relative.is_empty())
+            .unwrap_or_else(|| path.display().to_string());
+        on_skip(&shown, &reason);
+    }
+    FsError::Filtered
+}
+
+/// Formats a path relative to a base directory with `/` separators
+///
+/// # Arguments
+/// * `path` - The path to format
+/// * `base` - The directory the result is relative to
+///
+/// # Returns
+/// * `Option<String>` - The relative path, `None` if `path` is not below `base`
+fn relative_path(path: &Path, base: &Path) -> Option<String> {
+    let relative = path.strip_prefix(base).ok()?;
+    Some(relative.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Checks an entry against the `.gitignore` files of its ancestors within the traversal
+///
+/// Files are read once per directory and cached. Rules in deeper files take
+/// precedence, so a nested `.gitignore` can re-include what a parent ignored.
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `state` - State holding the traversal root and the cached rules
+///
+/// # Returns
+/// * `bool` - True if the entry is ignored
+fn is_gitignored(path: &Path, state: &mut TraversalState) -> bool {
+    let is_dir = state.source.is_dir(path);
+    let directories = path.ancestors()
+        .skip(1)
+        .take_while(|ancestor| ancestor.starts_with(&state.root))
+        .collect::<Vec<_>>();
+
+    let mut ignored = false;
+    for directory in directories.into_iter().rev() {
+        let gitignore = state.gitignores
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| Gitignore::from_file(&directory.join(".gitignore")));
+
+        let Some(gitignore) = gitignore else {
+            continue;
+        };
+        let Some(relative) = relative_path(path, directory) else {
+            continue;
+        };
+
+        if let Some(decision) = gitignore.matched(&relative, is_dir) {
+            ignored = decision;
+        }
+    }
+    ignored
+}
+
+/// Reads a directory, retrying transient failures with a short exponential backoff
+///
+/// Only the IO error of the read itself is retried; filtered or empty entries
+/// are not errors at this level and are never retried. Errors that another
+/// attempt cannot fix, such as a denied permission or a missing directory, are
+/// returned at once.
+///
+/// # Arguments
+/// * `path` - Path to the directory
+/// * `retries` - Maximum number of retries after the first attempt
+/// * `source` - Where the directory is read from
+///
+/// # Returns
+/// * `std::io::Result<Vec<io::Result<PathBuf>>>` - The paths of the entries or the last error
+fn read_dir_with_retry(path: &Path, retries: u32, source: &dyn DirSource) -> std::io::Result<Vec<io::Result<PathBuf>>> {
+    let mut attempt = 0;
+    loop {
+        match source.read_dir(path) {
+            Ok(entries) => return Ok(entries),
+            Err(e) if attempt < retries && is_transient(&e) => {
+                thread::sleep(Duration::from_millis(50 << attempt.min(6)));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Checks whether a failed read may succeed when attempted again
+///
+/// Interrupted, timed out and would-block reads are transient, as are the I/O
+/// errors and stale handles of network mounts (`EIO` and `ESTALE` on Unix).
+///
+/// # Arguments
+/// * `error` - The error of the failed read
+///
+/// # Returns
+/// * `bool` - True if the read is worth retrying
+fn is_transient(error: &io::Error) -> bool {
+    match error.kind() {
+        io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::StaleNetworkFileHandle => true,
+        #[cfg(unix)]
+        _ => error.raw_os_error() == Some(EIO),
+        #[cfg(not(unix))]
+        _ => false,
+    }
+}
+
+/// `EIO`, which has the same value on every Unix
+#[cfg(unix)]
+const EIO: i32 = 5;
+
+/// Checks whether a directory has at least one visible entry, without traversing it
+///
+/// # Arguments
+/// * `path` - Path to the directory
+/// * `show_hidden` - Whether hidden entries count as visible
+/// * `source` - Where the directory is read from
+///
+/// # Returns
+/// * `bool` - True if the directory could be read and has a visible entry
+fn has_entries(path: &Path, show_hidden: bool, source: &dyn DirSource) -> bool {
+    source.read_dir(path)
+        .map(|entries| entries.into_iter().any(|entry| {
+            entry.is_ok_and(|entry| show_hidden || !get_path_name(&entry).starts_with('.'))
+        }))
+        .unwrap_or(false)
+}
+
+/// Finds the first opt-out marker file present in a directory
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `options` - Configuration options containing the marker names
+/// * `source` - Where the directory is read from
+///
+/// # Returns
+/// * `Option<&str>` - The marker found, `None` if the entry is not a marked directory
+fn find_skip_marker<'a>(path: &Path, options: &'a FolderStructureOptions, source: &dyn DirSource) -> Option<&'a str> {
+    if options.skip_dir_with.is_empty() || !source.is_dir(path) {
+        return None;
+    }
+
+    options.skip_dir_with.iter()
+        .find(|marker| source.exists(&path.join(marker)))
+        .map(String::as_str)
+}
+
+/// Checks whether an entry is a folder to list collapsed into its entry count
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `options` - Configuration options containing the folder names
+/// * `source` - Where the entry is read from
+///
+/// # Returns
+/// * `bool` - True if the entry is a directory named like one of `collapse_dirs`
+fn is_collapsed(path: &Path, options: &FolderStructureOptions, source: &dyn DirSource) -> bool {
+    if options.collapse_dirs.is_empty() || !source.is_dir(path) {
+        return false;
+    }
+
+    let name = get_path_name(path);
+    options.collapse_dirs.iter().any(|collapsed| {
+        if options.ignore_case { collapsed.eq_ignore_ascii_case(&name) } else { *collapsed == name }
+    })
+}
+
+/// Checks whether a path is a Windows reparse point (junction, symlink, ...)
+///
+/// # Arguments
+/// * `path` - Path to the entry
+///
+/// # Returns
+/// * `bool` - True if the entry carries the reparse-point attribute
+#[cfg(windows)]
+fn is_reparse_point(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+    fs::symlink_metadata(path)
+        .is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0)
+}
+
+/// Reparse points only exist on Windows
+#[cfg(not(windows))]
+fn is_reparse_point(_path: &Path) -> bool {
+    false
+}
+
+/// Determines if an entry should be skipped during processing
+///
+/// # Arguments
+/// * `path` - Path to the entry
+/// * `options` - Configuration options for filtering
+/// * `source` - Where the entry is read from
+///
+/// # Returns
+/// * `Option<ExclusionReason>` - Why the entry should be skipped, `None` if it is kept
+fn should_skip_entry(path: &Path, options: &FolderStructureOptions, source: &dyn DirSource) -> Option<ExclusionReason> {
+    let file_name = path.file_name()
+        .and_then(|n| n.to_str());
+    
+    match file_name {
+        Some(name) if name.starts_with('.') && !options.show_hidden => Some(ExclusionReason::Hidden),
+        Some(name) => should_include_item(name, source.is_dir(path), options).err(),
+        // Names that are not valid UTF-8 cannot be matched against the filters
+        None => Some(ExclusionReason::InvalidName),
+    }
+}
+
+/// Recursively sorts the children of every folder with `sort_items`
+///
+/// # Arguments
+/// * `item` - The item to sort in place
+/// * `options` - Configuration options selecting the order
+fn sort_structure(item: &mut Item, options: &FolderStructureOptions) {
+    if let Item::Folder(_, items, ..) = item {
+        items.sort_by(|a, b| sort_items(a, b, options));
+        items.iter_mut().for_each(|child| sort_structure(child, options));
+    }
+}
+
+/// Comparison function for sorting items
+///
+/// Pinned names come first, then folders and files are grouped as selected by
+/// `grouping`, then the selected sort key applies.
+///
+/// # Arguments
+/// * `a` - First item to compare
+/// * `b` - Second item to compare
+/// * `options` - Configuration options containing the pinned names, grouping and sort order
+///
+/// # Returns
+/// * `Ordering` - The ordering relationship between the items
+fn sort_items(a: &Item, b: &Item, options: &FolderStructureOptions) -> Ordering {
+    // Pinned names come first, keeping the order in which they were listed
+    let pin_a = options.pinned_names.iter().position(|pin| pin == a.name());
+    let pin_b = options.pinned_names.iter().position(|pin| pin == b.name());
+    match (pin_a, pin_b) {
+        (Some(index_a), Some(index_b)) => return index_a.cmp(&index_b),
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (None, None) => {}
+    }
+
+    let group = |item: &Item| {
+        let is_file = matches!(item, Item::File(..));
+        match options.grouping {
+            Grouping::DirsFirst => is_file,
+            Grouping::DirsLast => !is_file,
+            Grouping::Mixed => false,
+        }
+    };
+    group(a).cmp(&group(b)).then_with(|| {
+        let ordering = compare_by_key(a, b, options.sort_key, options.sort_case_insensitive);
+        if options.reverse_sort { ordering.reverse() } else { ordering }
+    })
+}
+
+/// Compares two items by a sort key, ties broken by name
+///
+/// Sizes and times sort largest and newest first; unknown values come last.
+///
 /// # Arguments
-/// * `root` - The root item of the structure
+/// * `a` - First item to compare
+/// * `b` - Second item to compare
+/// * `key` - The attribute to compare
+/// * `case_insensitive` - Whether names are compared by their lowercase form first
+///
+/// # Returns
+/// * `Ordering` - The ordering relationship between the items
+fn compare_by_key(a: &Item, b: &Item, key: SortKey, case_insensitive: bool) -> Ordering {
+    let by_name = if case_insensitive {
+        a.name().to_lowercase().cmp(&b.name().to_lowercase()).then_with(|| a.name().cmp(b.name()))
+    } else {
+        a.name().cmp(b.name())
+    };
+    match key {
+        SortKey::Name => by_name,
+        SortKey::Size => b.metadata().size.cmp(&a.metadata().size).then(by_name),
+        SortKey::Modified => b.metadata().modified.cmp(&a.metadata().modified).then(by_name),
+    }
+}
+
+/// Creates a folder item with the given contents
+///
+/// # Arguments
+/// * `path` - Path to the folder
+/// * `name` - Name of the folder
+/// * `items` - Contents of the folder
+/// * `options` - Configuration options
+///
+/// # Returns
+/// * `FsResult<Item>` - The folder item or an error
+fn create_folder_item(path: &Path, name: String, items: Vec<Item>, options: &FolderStructureOptions) -> FsResult<Item> {
+    // Empty folders may sit below a kept folder, which is only known once the tree is built
+    if items.is_empty() && !options.show_empty_folder && options.keep_dirs.is_empty() {
+        return Err(FsError::EmptyFolder);
+    }
+
+    let folder_name = if path.as_os_str() == "." { 
+        ".".to_string() 
+    } else { 
+        name 
+    };
+
+    let metadata = ItemMetadata {
+        symlink: is_symlink(path),
+        mode: read_mode(path, options),
+        ..ItemMetadata::default()
+    };
+    Ok(Item::Folder(folder_name, items, None, metadata))
+}
+
+/// Records the permissions of a folder listed without its children
+///
+/// # Arguments
+/// * `item` - The item built for the path
+/// * `path` - Path the item was built from
+/// * `options` - Configuration options deciding whether permissions are shown
+///
+/// # Returns
+/// * `Item` - The item, with its permissions when `show_perms` is set
+fn with_mode(mut item: Item, path: &Path, options: &FolderStructureOptions) -> Item {
+    if let Item::Folder(.., metadata) = &mut item {
+        metadata.mode = metadata.mode.or_else(|| read_mode(path, options));
+    }
+    item
+}
+
+/// Reads the permission bits of a path when `show_perms` is set
+fn read_mode(path: &Path, options: &FolderStructureOptions) -> Option<u32> {
+    options.show_perms.then(|| fs::metadata(path).ok()).flatten().and_then(|m| EntryMetadata::from(&m).mode)
+}
+
+/// Creates a folder item that is listed without traversing its children
+///
+/// # Arguments
+/// * `name` - Name of the folder
+/// * `note` - Marker displayed after the name
+///
+/// # Returns
+/// * `Item` - The marked folder item
+fn create_marked_folder(name: String, note: &str) -> Item {
+    let mut folder = create_truncated_folder(name);
+    if let Item::Folder(.., metadata) = &mut folder {
+        metadata.note = Some(note.to_string());
+    }
+    folder
+}
+
+/// Creates a symlinked folder item that is listed without traversing its children
+///
+/// # Arguments
+/// * `name` - Name of the link
+/// * `target` - Where the link points, displayed after the name
+///
+/// # Returns
+/// * `Item` - The link folder item
+fn create_link_folder(name: String, target: String) -> Item {
+    let mut folder = create_truncated_folder(name);
+    if let Item::Folder(.., metadata) = &mut folder {
+        metadata.symlink = true;
+        metadata.link = Some(target);
+    }
+    folder
+}
+
+/// Creates a folder item that is listed without traversing its children
+///
+/// # Arguments
+/// * `name` - Name of the folder
+///
+/// # Returns
+/// * `Item` - The truncated folder item
+fn create_truncated_folder(name: String) -> Item {
+    let metadata = ItemMetadata {
+        truncated: true,
+        ..ItemMetadata::default()
+    };
+    Item::Folder(name, Vec::new(), None, metadata)
+}
+
+/// Connector strings drawing the tree, all `indent` characters wide
+struct TreeMarkers {
+    /// Connector of an entry followed by siblings
+    branch: String,
+
+    /// Connector of the last entry of a folder
+    last_branch: String,
+
+    /// Prefix continuing the line of a folder that has more entries below
+    vertical: String,
+
+    /// Prefix below the last entry of a folder
+    blank: String,
+}
+
+impl TreeMarkers {
+    /// Builds the connectors for the given options
+    ///
+    /// Each connector is its glyph, horizontal lines up to one character short
+    /// of the indent width, then a space, so `├── ` at the default width of 4.
+    ///
+    /// # Arguments
+    /// * `option` - Configuration options selecting the glyphs and the indent width
+    ///
+    /// # Returns
+    /// * `TreeMarkers` - The connectors of the `charset`, else box-drawing ones unless `ascii` is set
+    fn for_options(option: &FolderStructureOptions) -> TreeMarkers {
+        let (branch, last_branch, vertical) = match option.charset {
+            Some(Charset::Utf8) => ("├── ", "└── ", "│\u{a0}\u{a0} "),
+            Some(Charset::Ascii) => ("|-- ", "`-- ", "|   "),
+            None => return TreeMarkers::with_glyphs(option),
+        };
+        TreeMarkers {
+            branch: branch.to_string(),
+            last_branch: last_branch.to_string(),
+            vertical: vertical.to_string(),
+            blank: "    ".to_string(),
+        }
+    }
+
+    /// Builds the connectors from the `ascii` and `indent` options
+    fn with_glyphs(option: &FolderStructureOptions) -> TreeMarkers {
+        let (tee, corner, pipe, line) = if option.ascii { ('|', '`', '|', '-') } else { ('├', '└', '│', '─') };
+        let width = option.indent;
+        let connector = |glyph: char| format!("{}{} ", glyph, line.to_string().repeat(width - 2));
+
+        TreeMarkers {
+            branch: connector(tee),
+            last_branch: connector(corner),
+            vertical: format!("{}{}", pipe, " ".repeat(width - 1)),
+            blank: " ".repeat(width),
+        }
+    }
+
+    /// Connector of an entry, depending on whether it is the last of its folder
+    fn connector(&self, is_last: bool) -> &str {
+        if is_last { &self.last_branch } else { &self.branch }
+    }
+}
+
+/// Where an item is printed within the tree
+#[derive(Clone, Copy)]
+struct LinePosition<'a> {
+    /// Current line prefix for proper tree formatting
+    prefix: &'a str,
+
+    /// Whether this is the last item in its level
+    is_last: bool,
+
+    /// Depth of the item, 0 for the root
+    depth: usize,
+
+    /// Path of the item relative to the root
+    path: &'a str,
+
+    /// Total size of the parent folder, `None` for the root
+    parent_size: Option<u64>,
+
+    /// Column at which annotations start, `None` to not align them
+    column: Option<usize>,
+
+    /// Widths of the depth number and relative path columns, `None` when not shown
+    depth_column: Option<(usize, usize)>,
+
+    /// Width of the bracketed depth number, `None` when not shown
+    depth_marker: Option<usize>,
+
+    /// Connector strings drawing the tree
+    markers: &'a TreeMarkers,
+}
+
+/// Prints a single item in the structure with proper formatting
+///
+/// # Arguments
+/// * `out` - The sink to write to
+/// * `item` - The item to print
+/// * `position` - Where the item is printed within the tree
 /// * `option` - Configuration options for display
-pub fn print_tree(root: &Item, option: &FolderStructureOptions) {
-    print_structure(root, "", true, option);
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+fn print_structure<W: Write>(out: &mut W, item: &Item, position: &LinePosition, option: &FolderStructureOptions) -> io::Result<()> {
+    if position.depth < option.min_depth {
+        return print_hidden_level(out, item, position, option);
+    }
+
+    let prefix = position.prefix;
+    let markers = position.markers;
+    let marker = markers.connector(position.is_last);
+    let next_prefix = if position.is_last { &markers.blank } else { &markers.vertical };
+    let annotation = format_annotations(item, position.parent_size, option);
+    let lead = format_lead(Some(item), position, option);
+    // Top lines (the root, or the entries at `min_depth`) are printed without connector
+    let is_top = position.depth == option.min_depth;
+    let connector = if is_top { String::new() } else { format!("{}{}", prefix, marker) };
+
+    // With a line width, names are shortened and annotations pulled left so lines fit
+    let label = entry_label(item, position.path, position.depth, option);
+    let label = match (option.line_width, position.column) {
+        // The shared column leaves room for the widest annotation it aligns, so every
+        // label stops before it and the annotations stay in line
+        (Some(_), Some(column)) => fit_label(&label, column.saturating_sub(connector.chars().count())),
+        (Some(width), None) => {
+            let room = width.saturating_sub(lead.chars().count() + annotation.chars().count());
+            fit_label(&label, room.saturating_sub(connector.chars().count()))
+        }
+        (None, _) => label,
+    };
+    let column = position.column;
+
+    match item {
+        Item::File(..) => {
+            write_line(out, &lead, &connector, &label, name_style(item, option), &annotation, column)?;
+        }
+        Item::Folder(_, items, _, metadata) => {
+            // Skip empty folders if show_empty_folder is false
+            if !is_displayed(item, option) {
+                return Ok(());
+            }
+
+            write_line(out, &lead, &connector, &label, name_style(item, option), &annotation, column)?;
+            
+            // Set up the prefix for children
+            let new_prefix = if is_top {
+                // For the children of a top line
+                markers.blank.clone()
+            } else {
+                // For nested children
+                format!("{}{}", prefix, next_prefix)
+            };
+            
+            // Align the children's annotations if requested
+            let child_column = match option.alignment {
+                Some(Alignment::PerDirectory) => items.iter()
+                    .filter(|child| is_displayed(child, option))
+                    .map(|child| {
+                        let child_path = join_relative(position.path, child.name());
+                        new_prefix.chars().count() + option.indent + label_width(child, &child_path, position.depth + 1, option)
+                    })
+                    .max()
+                    .map(|column| {
+                        let widest_annotation = items.iter()
+                            .filter(|child| is_displayed(child, option))
+                            .map(|child| format_annotations(child, metadata.size, option).chars().count())
+                            .max()
+                            .unwrap_or(0);
+                        clamp_column(column, lead.chars().count(), widest_annotation, option)
+                    }),
+                _ => position.column,
+            };
+
+            // Print all children, up to `max_entries` of those displayed
+            let mut entries = group_children(items, option);
+            entries.retain(|entry| match entry {
+                ChildEntry::Item(child) => is_displayed(child, option),
+                ChildEntry::EmptyRun(_) => true,
+            });
+            let omitted = match option.max_entries {
+                Some(max) if entries.len() > max => entries.drain(max..)
+                    .map(|entry| match entry {
+                        ChildEntry::Item(_) => 1,
+                        ChildEntry::EmptyRun(count) => count,
+                    })
+                    .sum(),
+                _ => 0,
+            };
+            for (i, entry) in entries.iter().enumerate() {
+                let is_last_child = i == entries.len() - 1 && omitted == 0;
+                match entry {
+                    ChildEntry::Item(child) => {
+                        let child_path = join_relative(position.path, child.name());
+                        let child_position = LinePosition {
+                            prefix: &new_prefix,
+                            is_last: is_last_child,
+                            depth: position.depth + 1,
+                            path: &child_path,
+                            parent_size: metadata.size,
+                            column: child_column,
+                            ..*position
+                        };
+                        print_structure(out, child, &child_position, option)?;
+                    }
+                    ChildEntry::EmptyRun(count) => {
+                        let marker = markers.connector(is_last_child);
+                        let lead = format_lead(None, position, option);
+                        writeln!(out, "{}{}{}[{} empty folders]", lead, new_prefix, marker, count)?;
+                    }
+                }
+            }
+            if omitted > 0 {
+                let lead = format_lead(None, position, option);
+                writeln!(out, "{}{}{}... and {} more", lead, new_prefix, markers.last_branch, omitted)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the descendants of an item above `min_depth` without printing the item itself
+///
+/// The hidden levels take no room: each displayed child at `min_depth` becomes
+/// a top line, labelled with its relative path, and its subtree is drawn below
+/// it as usual.
+///
+/// # Arguments
+/// * `out` - The sink to write to
+/// * `item` - The hidden item
+/// * `position` - Where the item would be printed
+/// * `option` - Configuration options for display
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+fn print_hidden_level<W: Write>(out: &mut W, item: &Item, position: &LinePosition, option: &FolderStructureOptions) -> io::Result<()> {
+    let Item::Folder(_, items, _, metadata) = item else {
+        return Ok(());
+    };
+
+    for child in items.iter().filter(|child| is_displayed(child, option)) {
+        let child_path = join_relative(position.path, child.name());
+        let child_position = LinePosition {
+            depth: position.depth + 1,
+            path: &child_path,
+            parent_size: metadata.size,
+            ..*position
+        };
+        print_structure(out, child, &child_position, option)?;
+    }
+    Ok(())
+}
+
+/// Writes a line, padding its content so the annotation starts at the given column
+///
+/// # Arguments
+/// * `out` - The sink to write to
+/// * `lead` - Text printed before the tree connectors, possibly empty
+/// * `connector` - The prefix and connector of the line
+/// * `label` - The name of the entry
+/// * `style` - ANSI style applied to the label, `None` for plain text
+/// * `annotation` - The annotation following the name, possibly empty
+/// * `column` - Column at which the annotation starts, `None` to not align it
+///
+/// # Returns
+/// * `io::Result<()>` - The error returned by the sink, if any
+fn write_line<W: Write>(out: &mut W, lead: &str, connector: &str, label: &str, style: Option<&str>, annotation: &str, column: Option<usize>) -> io::Result<()> {
+    // Escape codes take no room on screen, so padding only counts the visible text
+    let padding = match column {
+        Some(column) if !annotation.is_empty() => column.saturating_sub(connector.chars().count() + label.chars().count()),
+        _ => 0,
+    };
+    match style {
+        Some(style) => writeln!(out, "{}{}\x1b[{}m{}\x1b[0m{:padding$}{}", lead, connector, style, label, "", annotation),
+        None => writeln!(out, "{}{}{}{:padding$}{}", lead, connector, label, "", annotation),
+    }
+}
+
+/// Picks the ANSI style of an entry's name when colors are enabled
+///
+/// Symlinks are cyan, folders bold blue and executable files green.
+///
+/// # Arguments
+/// * `item` - The item being printed
+/// * `option` - Configuration options deciding whether colors are used
+///
+/// # Returns
+/// * `Option<&'static str>` - The SGR parameters, `None` for plain text
+fn name_style(item: &Item, option: &FolderStructureOptions) -> Option<&'static str> {
+    if !option.color {
+        return None;
+    }
+
+    match item {
+        _ if item.metadata().symlink => Some("36"),
+        Item::Folder(..) => Some("1;34"),
+        Item::File(_, metadata) if metadata.executable => Some("32"),
+        Item::File(..) => None,
+    }
+}
+
+/// Formats the columns printed left of the tree connectors
+///
+/// # Arguments
+/// * `item` - The item printed at `position`, `None` for a line among its children
+///   without an entry, such as a collapsed run or the `... and N more` line
+/// * `position` - Where the item is printed within the tree
+/// * `option` - Configuration options deciding which columns are shown
+///
+/// # Returns
+/// * `String` - The columns, each with its trailing separator, or an empty string
+fn format_lead(item: Option<&Item>, position: &LinePosition, option: &FolderStructureOptions) -> String {
+    let (size, path) = match item {
+        Some(item) => (Some(item.metadata().size), position.path),
+        None => (None, ""),
+    };
+    let depth = if item.is_some() { position.depth } else { position.depth + 1 };
+    format!(
+        "{}{}{}{}{}",
+        format_du_column(size, option),
+        format_perms_column(item.map(|item| item.metadata().mode), option),
+        format_mtime_column(item.map(|item| item.metadata().modified), option),
+        format_depth_column(position.depth_column, depth, path),
+        format_depth_marker(position.depth_marker, depth)
+    )
+}
+
+/// Formats the bracketed depth number printed left of the tree connectors
+///
+/// # Arguments
+/// * `width` - Width of the deepest depth number, `None` when not shown
+/// * `depth` - Depth of the line
+///
+/// # Returns
+/// * `String` - The marker, including a trailing space, or an empty string
+fn format_depth_marker(width: Option<usize>, depth: usize) -> String {
+    match width {
+        Some(width) => format!("[{:>width$}] ", depth),
+        None => String::new(),
+    }
+}
+
+/// Formats the zero-padded depth and relative path printed left of the tree connectors
+///
+/// # Arguments
+/// * `widths` - Widths of the depth and path columns, `None` when not shown
+/// * `depth` - Depth of the line
+/// * `path` - Relative path of the line, empty for lines without an entry
+///
+/// # Returns
+/// * `String` - The column text, including a trailing separator, or an empty string
+fn format_depth_column(widths: Option<(usize, usize)>, depth: usize, path: &str) -> String {
+    match widths {
+        Some((depth_width, path_width)) => format!("{:0depth_width$} {:<path_width$}  ", depth, path),
+        None => String::new(),
+    }
+}
+
+/// Shortens a label with an ellipsis so it takes at most the given number of columns
+///
+/// A folder's trailing `/` is kept, and at least one character of the name is,
+/// so a line may still overflow when its prefix and annotation are too wide.
+///
+/// # Arguments
+/// * `label` - The label to fit
+/// * `room` - Columns available for the label
+///
+/// # Returns
+/// * `String` - The label, shortened if it does not fit
+fn fit_label(label: &str, room: usize) -> String {
+    if label.chars().count() <= room {
+        return label.to_string();
+    }
+
+    let (name, suffix) = match label.strip_suffix('/') {
+        Some(name) => (name, "/"),
+        None => (label, ""),
+    };
+    let kept = room.saturating_sub(1 + suffix.len()).max(1);
+    format!("{}…{}", name.chars().take(kept).collect::<String>(), suffix)
+}
+
+/// Formats the size column printed at the start of each line with `du`
+///
+/// # Arguments
+/// * `size` - Size of the line's entry, `Some(None)` when unknown, `None` for lines without an entry
+/// * `option` - Configuration options deciding whether the column is shown
+///
+/// # Returns
+/// * `String` - The column text, including a trailing separator, or an empty string
+fn format_du_column(size: Option<Option<u64>>, option: &FolderStructureOptions) -> String {
+    if !option.du {
+        return String::new();
+    }
+
+    match size {
+        Some(Some(size)) => format!("[{:>10}]  ", format_size(size)),
+        Some(None) => format!("[{:>10}]  ", "?"),
+        None => " ".repeat(14),
+    }
+}
+
+/// Formats the permissions column printed after the `du` column with `show_perms`
+///
+/// # Arguments
+/// * `mode` - Permission bits of the line's entry, `Some(None)` when unknown, `None` for lines without an entry
+/// * `option` - Configuration options deciding whether the column is shown
+///
+/// # Returns
+/// * `String` - The column text, including a trailing separator, or an empty string
+fn format_perms_column(mode: Option<Option<u32>>, option: &FolderStructureOptions) -> String {
+    if !option.show_perms {
+        return String::new();
+    }
+
+    match mode {
+        Some(Some(mode)) => format!("{}  ", format_mode(mode)),
+        Some(None) => format!("{}  ", "?".repeat(9)),
+        None => " ".repeat(11),
+    }
+}
+
+/// Formats the modification time column printed before the tree connectors with `mtime_style`
+///
+/// The column is as wide as an ISO timestamp whatever the style, so relative
+/// times line up too.
+///
+/// # Arguments
+/// * `modified` - Modification time of the line's entry, `Some(None)` when unknown, `None` for lines without an entry
+/// * `option` - Configuration options deciding whether and how the column is shown
+///
+/// # Returns
+/// * `String` - The column text, including a trailing separator, or an empty string
+fn format_mtime_column(modified: Option<Option<SystemTime>>, option: &FolderStructureOptions) -> String {
+    let Some(style) = option.mtime_style else {
+        return String::new();
+    };
+
+    let text = match (modified, style) {
+        (Some(Some(time)), TimeStyle::Iso) => format_timestamp(time),
+        (Some(Some(time)), TimeStyle::Relative) => format_relative_time(time, SystemTime::now()),
+        (Some(None), _) => "-".to_string(),
+        (None, _) => String::new(),
+    };
+    format!("{:<16}  ", text)
+}
+
+/// Converts permission bits to their `ls -l` form, e.g. `rwxr-xr-x`
+///
+/// The setuid and setgid bits replace the owner and group execute letters
+/// with `s`, and the sticky bit replaces the others' one with `t`, in
+/// uppercase when the matching execute bit is not set.
+///
+/// # Arguments
+/// * `mode` - The permission bits
+///
+/// # Returns
+/// * `String` - The nine permission letters
+fn format_mode(mode: u32) -> String {
+    // (read, write, execute, special bit, special letter) for owner, group and others
+    let classes = [(0o400, 0o200, 0o100, 0o4000, 's'), (0o040, 0o020, 0o010, 0o2000, 's'), (0o004, 0o002, 0o001, 0o1000, 't')];
+    let mut letters = String::with_capacity(9);
+    for (read, write, execute, special, letter) in classes {
+        letters.push(if mode & read != 0 { 'r' } else { '-' });
+        letters.push(if mode & write != 0 { 'w' } else { '-' });
+        letters.push(match (mode & execute != 0, mode & special != 0) {
+            (true, true) => letter,
+            (false, true) => letter.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    letters
+}
+
+/// Joins a relative parent path and a child name, the root being `.`
+fn join_relative(parent: &str, name: &str) -> String {
+    if parent == "." {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// Computes the widest relative path and the deepest depth among the displayed items
+///
+/// # Arguments
+/// * `item` - The item to measure
+/// * `path` - Relative path of the item
+/// * `depth` - Depth of the item
+/// * `option` - Configuration options for display
+///
+/// # Returns
+/// * `(usize, usize)` - The deepest depth and the widest path
+fn measure_depth_column(item: &Item, path: &str, depth: usize, option: &FolderStructureOptions) -> (usize, usize) {
+    if !is_displayed(item, option) {
+        return (0, 0);
+    }
+
+    let mut widest = (depth, path.chars().count());
+    if let Item::Folder(_, items, ..) = item {
+        for child in items {
+            let (child_depth, child_width) = measure_depth_column(child, &join_relative(path, child.name()), depth + 1, option);
+            widest = (widest.0.max(child_depth), widest.1.max(child_width));
+        }
+    }
+    widest
+}
+
+/// Text printed for an item: its name, or its relative path with `full_path`
+///
+/// Entries at `min_depth` start the visible tree below hidden ancestors, so
+/// they always show their relative path to keep their context. Folders end
+/// with `/`, except the root.
+///
+/// # Arguments
+/// * `item` - The item being printed
+/// * `path` - Path of the item relative to the root, `.` for the root
+/// * `depth` - Depth of the item, 0 for the root
+/// * `option` - Configuration options for display
+///
+/// # Returns
+/// * `String` - The label of the line
+fn entry_label(item: &Item, path: &str, depth: usize, option: &FolderStructureOptions) -> String {
+    let name = if depth == 0 {
+        item.name()
+    } else if option.full_path || depth == option.min_depth {
+        path
+    } else {
+        item.name()
+    };
+    match item {
+        Item::Folder(..) if depth > 0 => format!("{}/", name),
+        _ => name.to_string(),
+    }
 }
 
-/// Determines if a file should be included based on extension filters
+/// Width of an item's displayed label, including the trailing `/` of folders
+fn label_width(item: &Item, path: &str, depth: usize, option: &FolderStructureOptions) -> usize {
+    entry_label(item, path, depth, option).chars().count()
+}
+
+/// Pulls an annotation column left so the widest annotation it aligns still fits in `line_width`
 ///
 /// # Arguments
-/// * `file_name` - Name of the file to check
-/// * `options` - Filter options containing include/exclude patterns
+/// * `column` - The column the labels ask for
+/// * `lead_width` - Width of the columns printed left of the tree connectors
+/// * `annotation_width` - Width of the widest annotation aligned at the column
+/// * `option` - Configuration options holding the line width
 ///
 /// # Returns
-/// * `bool` - True if the file should be included
-fn should_include_file(file_name: &str, options: &FolderStructureOptions) -> bool {
-    // If both vectors are empty, include all files
-    if options.exclude_extension.is_empty() && options.include_extension_only.is_empty() {
-        return true;
-    }
-
-    // If exclude_extension is not empty, exclude files with matching extensions
-    if !options.exclude_extension.is_empty() {
-        return !options.exclude_extension.iter()
-            .any(|ext| file_name.ends_with(ext));
+/// * `usize` - The column, unchanged when no line width is set
+fn clamp_column(column: usize, lead_width: usize, annotation_width: usize, option: &FolderStructureOptions) -> usize {
+    match option.line_width {
+        Some(width) => column.min(width.saturating_sub(lead_width + annotation_width)),
+        None => column,
     }
+}
 
-    // If include_extension_only is not empty, only include files with matching extensions
-    if !options.include_extension_only.is_empty() {
-        return options.include_extension_only.iter()
-            .any(|ext| file_name.ends_with(ext));
+/// Computes the widest annotation of an item and its displayed descendants
+///
+/// # Arguments
+/// * `item` - The item to measure
+/// * `parent_size` - Total size of the item's parent folder, `None` for the root
+/// * `option` - Configuration options for display
+///
+/// # Returns
+/// * `usize` - The width of the widest annotation
+fn max_annotation_width(item: &Item, parent_size: Option<u64>, option: &FolderStructureOptions) -> usize {
+    let own = format_annotations(item, parent_size, option).chars().count();
+    match item {
+        Item::Folder(_, items, _, metadata) => items.iter()
+            .filter(|child| is_displayed(child, option))
+            .map(|child| max_annotation_width(child, metadata.size, option))
+            .fold(own, usize::max),
+        Item::File(..) => own,
     }
-
-    true
 }
 
-/// Determines if an item should be included based on name filters
+/// Computes the widest line content (prefix, connector and name) in the tree
+///
+/// Mirrors the layout of `print_structure`: top lines (the root, or the entries
+/// at `min_depth`) are printed bare, and each level below adds an `indent`-wide
+/// prefix before the `indent`-wide connector.
 ///
 /// # Arguments
-/// * `item_name` - Name of the item to check
-/// * `options` - Filter options containing regex patterns
+/// * `item` - The item to measure
+/// * `path` - Path of the item relative to the root, `.` for the root
+/// * `depth` - Depth of the item, 0 for the root
+/// * `prefix_width` - Width of the item's prefix, `None` for top lines
+/// * `option` - Configuration options for display
 ///
 /// # Returns
-/// * `bool` - True if the item should be included
-fn should_include_item(item_name: &str, options: &FolderStructureOptions) -> bool {
-    // If both vectors are empty, include all files
-    if options.exclude_by_filter.is_empty() {
-        return true;
+/// * `usize` - The widest line content among the item and its descendants
+fn max_line_width(item: &Item, path: &str, depth: usize, prefix_width: Option<usize>, option: &FolderStructureOptions) -> usize {
+    if !is_displayed(item, option) {
+        return 0;
     }
 
-    // Check if any regex pattern matches
-    !options.exclude_by_filter
-        .iter()
-        .any(|re| re.is_match(item_name))
+    let own = match prefix_width {
+        None if depth < option.min_depth => 0,
+        None => label_width(item, path, depth, option),
+        Some(width) => width + option.indent + label_width(item, path, depth, option),
+    };
+    match item {
+        Item::Folder(_, items, ..) => {
+            let child_prefix_width = match prefix_width {
+                None if depth < option.min_depth => None,
+                None => Some(option.indent),
+                Some(width) => Some(width + option.indent),
+            };
+            items.iter()
+                .map(|child| max_line_width(child, &join_relative(path, child.name()), depth + 1, child_prefix_width, option))
+                .fold(own, usize::max)
+        }
+        Item::File(..) => own,
+    }
 }
 
-/// Updates the has_terminal_file flag for all folders in the structure
+/// Determines if an item is displayed by the printers
+///
+/// Folders without any terminal file are hidden unless `show_empty_folder` is set.
 ///
 /// # Arguments
-/// * `item` - The item to update
+/// * `item` - The item to check
+/// * `option` - Configuration options for display
 ///
 /// # Returns
-/// * `bool` - True if this item or any of its children contain a terminal file
-fn update_has_terminal_file(item: &mut Item) -> bool {
+/// * `bool` - True if the item should be displayed
+pub(crate) fn is_displayed(item: &Item, option: &FolderStructureOptions) -> bool {
     match item {
-        Item::File(_) => true,
-        Item::Folder(_, items, has_terminal) => {
-            let contains_terminal = items.iter_mut().any(|item| update_has_terminal_file(item));
-            *has_terminal = Some(contains_terminal);
-            contains_terminal
+        Item::File(..) => true,
+        Item::Folder(_, _, has_terminal_file, _) => {
+            option.show_empty_folder || has_terminal_file.unwrap_or(false)
         }
     }
 }
 
-/// Extracts the name from a path
+/// Groups a folder's sorted children into the entries to print
+///
+/// When `group_empty_runs` is set, runs of two or more consecutive folders
+/// without any terminal file are merged into a single `EmptyRun` entry.
 ///
 /// # Arguments
-/// * `path` - The path to extract the name from
+/// * `items` - The sorted children of a folder
+/// * `option` - Configuration options for display
 ///
 /// # Returns
-/// * `String` - The extracted name
-fn get_path_name(path: &PathBuf) -> String {
-    path.file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .into_owned()
+/// * `Vec<ChildEntry>` - The entries to print, in order
+fn group_children<'a>(items: &'a [Item], option: &FolderStructureOptions) -> Vec<ChildEntry<'a>> {
+    if !option.group_empty_runs {
+        return items.iter().map(ChildEntry::Item).collect();
+    }
+
+    let mut entries = Vec::new();
+    let mut run = Vec::new();
+    for item in items {
+        if let Item::Folder(_, _, Some(false), _) = item {
+            run.push(item);
+            continue;
+        }
+        flush_empty_run(&mut entries, &mut run);
+        entries.push(ChildEntry::Item(item));
+    }
+    flush_empty_run(&mut entries, &mut run);
+
+    entries
 }
 
-/// Processes a file item
+/// Moves a pending run of empty folders into the entries, collapsing it if it has several folders
 ///
 /// # Arguments
-/// * `name` - Name of the file
-/// * `options` - Configuration options for filtering
-///
-/// # Returns
-/// * `FsResult<Item>` - The file item or a filtered error
-fn handle_file(name: String, options: &FolderStructureOptions) -> FsResult<Item> {
-    if should_include_file(&name, options) {
-        Ok(Item::File(name))
+/// * `entries` - The entries being built
+/// * `run` - The pending run, emptied by this call
+fn flush_empty_run<'a>(entries: &mut Vec<ChildEntry<'a>>, run: &mut Vec<&'a Item>) {
+    if run.len() > 1 {
+        entries.push(ChildEntry::EmptyRun(run.len()));
     } else {
-        Err(FsError::Filtered)
+        entries.extend(run.iter().map(|item| ChildEntry::Item(item)));
     }
+    run.clear();
 }
 
-/// Processes a directory and its contents
+/// Builds the annotation text displayed after an item's name
 ///
 /// # Arguments
-/// * `path` - Path to the directory
-/// * `options` - Configuration options for filtering
+/// * `item` - The item being printed
+/// * `parent_size` - Total size of the parent folder, `None` for the root
+/// * `option` - Configuration options for display
 ///
 /// # Returns
-/// * `FsResult<Vec<Item>>` - Vector of processed items or an error
-fn process_directory(path: &PathBuf, options: &FolderStructureOptions) -> FsResult<Vec<Item>> {
-    let mut items = Vec::new();
-    
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if should_skip_entry(&path, options) {
-            continue;
+/// * `String` - The annotations, each preceded by a space, or an empty string
+pub(crate) fn format_annotations(item: &Item, parent_size: Option<u64>, option: &FolderStructureOptions) -> String {
+    let metadata = item.metadata();
+    let mut annotation = String::new();
+
+    if let Some(link) = &metadata.link {
+        annotation.push_str(&format!(" -> {}", link));
+    }
+
+    if let Some(note) = &metadata.note {
+        annotation.push_str(&format!(" [{}]", note));
+    }
+
+    if metadata.highlighted {
+        annotation.push_str(" <== here");
+    }
+
+    // Why the entry passed the filters
+    if option.explain {
+        let explanation = match item {
+            Item::File(_, metadata) => metadata.explanation.clone().unwrap_or_default(),
+            Item::Folder(..) if parent_size.is_none() => "root".to_string(),
+            Item::Folder(name, _, has_terminal_file, metadata) => {
+                let content = if metadata.truncated {
+                    "listed without contents"
+                } else if has_terminal_file.unwrap_or(false) {
+                    "contains matching files"
+                } else {
+                    "empty folder shown"
+                };
+                should_include_item(name, true, option)
+                    .ok()
+                    .filter(|reason| *reason != InclusionReason::NoFilters)
+                    .map(|reason| format!("{}, {}", reason, content))
+                    .unwrap_or_else(|| content.to_string())
+            }
+        };
+        annotation.push_str(&format!(" <{}>", explanation));
+    }
+
+    // Human-readable size, `?` when the metadata could not be read
+    let size_shown = match item {
+        Item::File(..) => option.show_size,
+        Item::Folder(..) => option.show_size && option.show_folder_size,
+    };
+    if size_shown {
+        match metadata.size {
+            Some(size) => annotation.push_str(&format!(" ({})", format_size(size))),
+            None => annotation.push_str(" (?)"),
         }
+    }
 
-        match get_folder_structure(&path, options) {
-            Ok(item) => items.push(item),
-            Err(FsError::Filtered) | Err(FsError::EmptyFolder) => continue,
-            Err(e) => return Err(e),
+    // Inode and link count, hard-linked files being flagged
+    if let (Some(inode), Some(hard_links)) = (metadata.inode, metadata.hard_links) {
+        let noun = if hard_links == 1 { "link" } else { "links" };
+        let flag = if hard_links > 1 { ", hardlinked" } else { "" };
+        annotation.push_str(&format!(" [inode {}, {} {}{}]", inode, hard_links, noun, flag));
+    }
+
+    // Share of the parent's total size
+    if option.show_size_percent {
+        if let Some(parent_size) = parent_size {
+            match metadata.size {
+                Some(size) if parent_size > 0 => {
+                    let percent = (size as f64 * 100.0 / parent_size as f64).round();
+                    annotation.push_str(&format!(" ({}%)", percent));
+                }
+                Some(_) => annotation.push_str(" (0%)"),
+                None => annotation.push_str(" (?)"),
+            }
         }
     }
 
-    items.sort_by(sort_items);
-    Ok(items)
-}
+    // Active folders show their most recent change
+    if let (Item::Folder(..), Some(_), Some(modified)) = (item, option.active_within, metadata.modified) {
+        annotation.push_str(&format!(" [{}]", format_timestamp(modified)));
+    }
 
-/// Determines if an entry should be skipped during processing
-///
-/// # Arguments
-/// * `path` - Path to the entry
-/// * `options` - Configuration options for filtering
-///
-/// # Returns
-/// * `bool` - True if the entry should be skipped
-fn should_skip_entry(path: &PathBuf, options: &FolderStructureOptions) -> bool {
-    let file_name = path.file_name()
-        .and_then(|n| n.to_str());
-    
-    match file_name {
-        Some(name) => {
-            name.starts_with('.') || !should_include_item(name, options)
+    // Number of distinct file types directly inside the folder
+    if let Item::Folder(_, items, ..) = item {
+        if option.show_type_diversity || option.max_types.is_some() {
+            let types = count_file_types(items);
+            let flag = if option.max_types.is_some_and(|max| types > max) { "!" } else { "" };
+            let noun = if types == 1 { "type" } else { "types" };
+            annotation.push_str(&format!(" {{{} {}{}}}", types, noun, flag));
         }
-        None => true
     }
+
+    annotation
 }
 
-/// Comparison function for sorting items
+/// Gets the lowercased extension a file is grouped under, `(none)` if it has none
 ///
 /// # Arguments
-/// * `a` - First item to compare
-/// * `b` - Second item to compare
+/// * `name` - Name of the file
 ///
 /// # Returns
-/// * `Ordering` - The ordering relationship between the items
-fn sort_items(a: &Item, b: &Item) -> Ordering {
-    match (a, b) {
-        (Item::Folder(name1, ..), Item::Folder(name2, ..)) => name1.cmp(name2),
-        (Item::Folder(..), Item::File(..)) => Ordering::Less,
-        (Item::File(..), Item::Folder(..)) => Ordering::Greater,
-        (Item::File(name1), Item::File(name2)) => name1.cmp(name2),
-    }
+/// * `String` - The grouping key
+pub(crate) fn extension_key(name: &str) -> String {
+    Path::new(name).extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
 }
 
-/// Creates a folder item with the given contents
+/// Counts the distinct extensions among the files of a folder
+///
+/// Only immediate files are considered; files without an extension count as one `(none)` type.
 ///
 /// # Arguments
-/// * `path` - Path to the folder
-/// * `name` - Name of the folder
-/// * `items` - Contents of the folder
-/// * `options` - Configuration options
+/// * `items` - The children of the folder
 ///
 /// # Returns
-/// * `FsResult<Item>` - The folder item or an error
-fn create_folder_item(path: &PathBuf, name: String, items: Vec<Item>, options: &FolderStructureOptions) -> FsResult<Item> {
-    if items.is_empty() && !options.show_empty_folder {
-        return Err(FsError::EmptyFolder);
+/// * `usize` - The number of distinct file types
+fn count_file_types(items: &[Item]) -> usize {
+    items.iter()
+        .filter_map(|item| match item {
+            Item::File(name, _) => Some(extension_key(name)),
+            Item::Folder(..) => None,
+        })
+        .collect::<HashSet<_>>()
+        .len()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiles patterns the way the command line does before handing them to the builder
+    fn regexes(patterns: &[&str]) -> Vec<Regex> {
+        patterns.iter().map(|pattern| Regex::new(pattern).unwrap()).collect()
     }
 
-    let folder_name = if path.as_os_str() == "." { 
-        ".".to_string() 
-    } else { 
-        name 
-    };
+    #[test]
+    fn filter_mode_any_excludes_on_a_single_match() {
+        let options = FolderStructureOptionsBuilder::default()
+            .exclude_by_filter(regexes(&["gen", "test"]))
+            .build()
+            .unwrap();
 
-    Ok(Item::Folder(folder_name, items, None))
-}
+        assert_eq!(should_include_item("gen.rs", false, &options), Err(ExclusionReason::ExcludedPattern("gen".to_string())));
+        assert_eq!(should_include_item("gen_test.rs", false, &options), Err(ExclusionReason::ExcludedPattern("gen".to_string())));
+        assert_eq!(should_include_item("main.rs", false, &options), Ok(InclusionReason::NoPatternMatched));
+    }
 
-/// Prints a single item in the structure with proper formatting
-///
-/// # Arguments
-/// * `item` - The item to print
-/// * `prefix` - Current line prefix for proper tree formatting
-/// * `is_last` - Whether this is the last item in its level
-/// * `option` - Configuration options for display
-fn print_structure(item: &Item, prefix: &str, is_last: bool, option: &FolderStructureOptions) {
-    let marker = if is_last { "└── " } else { "├── " };
-    let next_prefix = if is_last { "    " } else { "│   " };
+    #[test]
+    fn filter_mode_all_excludes_only_when_every_pattern_matches() {
+        let options = FolderStructureOptionsBuilder::default()
+            .exclude_by_filter(regexes(&["gen", "test"]))
+            .filter_mode(FilterMode::All)
+            .build()
+            .unwrap();
 
-    match item {
-        Item::File(name) => {
-            println!("{}{}{}", prefix, marker, name);
+        assert_eq!(should_include_item("gen_test.rs", false, &options), Err(ExclusionReason::AllPatternsMatched));
+        assert_eq!(should_include_item("gen.rs", false, &options), Ok(InclusionReason::NotAllPatternsMatched));
+        assert_eq!(should_include_item("main.rs", false, &options), Ok(InclusionReason::NotAllPatternsMatched));
+    }
+
+    #[test]
+    fn filter_mode_all_keeps_ignore_patterns_separate() {
+        let options = FolderStructureOptionsBuilder::default()
+            .exclude_by_filter(regexes(&["gen", "test"]))
+            .ignore_patterns(regexes(&["target"]))
+            .filter_mode(FilterMode::All)
+            .build()
+            .unwrap();
+
+        // An ignore pattern neither joins the conjunction nor needs the other patterns to match
+        assert_eq!(should_include_item("gen_test.rs", false, &options), Err(ExclusionReason::AllPatternsMatched));
+        assert_eq!(should_include_item("target.log", false, &options), Err(ExclusionReason::ExcludedPattern("target".to_string())));
+        assert_eq!(should_include_item("gen.rs", false, &options), Ok(InclusionReason::NotAllPatternsMatched));
+    }
+
+    #[test]
+    fn filter_mode_does_not_change_include_patterns() {
+        let options = FolderStructureOptionsBuilder::default()
+            .include_by_filter(regexes(&["\\.rs$", "^lib"]))
+            .exclude_by_filter(regexes(&["gen", "test"]))
+            .filter_mode(FilterMode::All)
+            .build()
+            .unwrap();
+
+        assert_eq!(should_include_item("main.rs", false, &options), Ok(InclusionReason::IncludedPattern("\\.rs$".to_string())));
+        assert_eq!(should_include_item("notes.txt", false, &options), Err(ExclusionReason::NoIncludedPattern));
+        assert_eq!(should_include_item("gen_test.rs", false, &options), Err(ExclusionReason::AllPatternsMatched));
+    }
+
+    /// Builds an in-memory tree holding the given files, each with its size
+    fn memory_fs(files: &[(&str, u64)]) -> MemoryFs {
+        let mut source = MemoryFs::new();
+        for &(path, size) in files {
+            source.add_file(path, EntryMetadata { size, ..EntryMetadata::default() });
         }
-        Item::Folder(name, items, has_terminal_file) => {
-            // Skip empty folders if show_empty_folder is false
-            if !option.show_empty_folder && !has_terminal_file.unwrap_or(false) {
-                return;
-            }
+        source
+    }
 
-            // Print the current folder with proper prefix
-            if prefix.is_empty() {
-                // Root folder case
-                println!("{}", name);
-            } else {
-                println!("{}{}{}/", prefix, marker, name);
-            }
-            
-            // Set up the prefix for children
-            let new_prefix = if prefix.is_empty() {
-                // For root's children
-                String::from("    ")
-            } else {
-                // For nested children
-                format!("{}{}", prefix, next_prefix)
-            };
-            
-            // Print all children
-            for (i, item) in items.iter().enumerate() {
-                print_structure(item, &new_prefix, i == items.len() - 1, option);
-            }
+    /// Builds a file item of the given size
+    fn file(name: &str, size: u64) -> Item {
+        Item::File(name.to_string(), ItemMetadata { size: Some(size), ..ItemMetadata::default() })
+    }
+
+    /// Builds an empty folder item
+    fn folder(name: &str) -> Item {
+        Item::Folder(name.to_string(), Vec::new(), None, ItemMetadata::default())
+    }
+
+    /// Sorts items with `sort_items` and returns their names
+    fn sorted_names(mut items: Vec<Item>, options: &FolderStructureOptions) -> Vec<String> {
+        items.sort_by(|a, b| sort_items(a, b, options));
+        items.iter().map(|item| item.name().to_string()).collect()
+    }
+
+    #[test]
+    fn should_skip_entry_hides_dotfiles_unless_shown() {
+        let source = memory_fs(&[("root/.env", 1), ("root/main.rs", 1)]);
+        let options = FolderStructureOptionsBuilder::default().build().unwrap();
+        let shown = FolderStructureOptionsBuilder::default().show_hidden(true).build().unwrap();
+
+        assert_eq!(should_skip_entry(Path::new("root/.env"), &options, &source), Some(ExclusionReason::Hidden));
+        assert_eq!(should_skip_entry(Path::new("root/.env"), &shown, &source), None);
+        assert_eq!(should_skip_entry(Path::new("root/main.rs"), &options, &source), None);
+    }
+
+    #[test]
+    fn should_skip_entry_applies_include_patterns_to_files_only() {
+        let source = memory_fs(&[("root/src/main.rs", 1), ("root/notes.txt", 1)]);
+        let options = FolderStructureOptionsBuilder::default()
+            .include_by_filter(regexes(&["\\.rs$"]))
+            .build()
+            .unwrap();
+
+        assert_eq!(should_skip_entry(Path::new("root/src"), &options, &source), None);
+        assert_eq!(should_skip_entry(Path::new("root/src/main.rs"), &options, &source), None);
+        assert_eq!(should_skip_entry(Path::new("root/notes.txt"), &options, &source), Some(ExclusionReason::NoIncludedPattern));
+    }
+
+    #[test]
+    fn should_skip_entry_applies_exclude_patterns_to_folders() {
+        let source = memory_fs(&[("root/target/debug.log", 1)]);
+        let options = FolderStructureOptionsBuilder::default()
+            .exclude_by_filter(regexes(&["^target$"]))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            should_skip_entry(Path::new("root/target"), &options, &source),
+            Some(ExclusionReason::ExcludedPattern("^target$".to_string()))
+        );
+    }
+
+    #[test]
+    fn sort_items_groups_folders_first_then_sorts_by_name() {
+        let options = FolderStructureOptionsBuilder::default().build().unwrap();
+        let items = vec![file("b.rs", 1), folder("src"), file("a.rs", 1), folder("docs")];
+
+        assert_eq!(sorted_names(items, &options), ["docs", "src", "a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn sort_items_puts_pinned_names_first_in_listed_order() {
+        let options = FolderStructureOptionsBuilder::default()
+            .pinned_names(vec!["README.md".to_string(), "src".to_string()])
+            .build()
+            .unwrap();
+        let items = vec![folder("docs"), file("a.rs", 1), folder("src"), file("README.md", 1)];
+
+        assert_eq!(sorted_names(items, &options), ["README.md", "src", "docs", "a.rs"]);
+    }
+
+    #[test]
+    fn sort_items_by_size_puts_the_largest_first_and_breaks_ties_by_name() {
+        let options = FolderStructureOptionsBuilder::default()
+            .sort_key(SortKey::Size)
+            .grouping(Grouping::Mixed)
+            .build()
+            .unwrap();
+        let items = vec![file("small", 1), file("b_big", 10), file("a_big", 10)];
+
+        assert_eq!(sorted_names(items, &options), ["a_big", "b_big", "small"]);
+    }
+
+    #[test]
+    fn sort_items_reverses_the_key_but_not_the_grouping() {
+        let options = FolderStructureOptionsBuilder::default().reverse_sort(true).build().unwrap();
+        let items = vec![file("a.rs", 1), folder("docs"), file("b.rs", 1), folder("src")];
+
+        assert_eq!(sorted_names(items, &options), ["src", "docs", "b.rs", "a.rs"]);
+    }
+
+    #[test]
+    fn memory_source_feeds_the_size_filter() {
+        let source = memory_fs(&[("root/big.bin", 2048), ("root/small.txt", 10)]);
+        let options = FolderStructureOptionsBuilder::default().min_size(Some(1024)).build().unwrap();
+
+        let root = get_folder_structure_from_source(Path::new("root"), &options, &source).unwrap();
+        let names = root.iter().map(|(item, _)| item.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names, ["root", "big.bin"]);
+    }
+
+    #[test]
+    fn on_skip_receives_each_skipped_entry_with_its_reason() {
+        let source = memory_fs(&[("root/.env", 1), ("root/src/main.rs", 1), ("root/src/notes.txt", 1)]);
+        let skipped = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let collector = Arc::clone(&skipped);
+        let options = FolderStructureOptionsBuilder::default()
+            .include_extension_only(vec!["rs".to_string()])
+            .on_skip(Some(Arc::new(move |path: &str, reason: &ExclusionReason| {
+                collector.lock().unwrap().push((path.to_string(), reason.clone()));
+            })))
+            .build()
+            .unwrap();
+
+        get_folder_structure_from_source(Path::new("root"), &options, &source).unwrap();
+        assert_eq!(*skipped.lock().unwrap(), [
+            (".env".to_string(), ExclusionReason::Hidden),
+            ("src/notes.txt".to_string(), ExclusionReason::NotIncludedExtension),
+        ]);
+    }
+
+    #[test]
+    fn extension_matches_only_the_last_extension_for_a_single_entry() {
+        assert!(extension_matches("archive.tar.gz", "gz"));
+        assert!(extension_matches("archive.tar.gz", ".gz"));
+        assert!(!extension_matches("archive.tar.gz", "tar"));
+        assert!(extension_matches("archive.tar.gz", "tar.gz"));
+    }
+
+    #[test]
+    fn extension_matches_never_matches_names_without_extension() {
+        assert!(!extension_matches("README", "README"));
+        assert!(!extension_matches("README", "md"));
+        assert!(!extension_matches(".bashrc", "bashrc"));
+    }
+
+    /// Finds the item at the given names below a folder
+    fn find<'a>(item: &'a Item, names: &[&str]) -> Option<&'a Item> {
+        names.iter().try_fold(item, |item, name| match item {
+            Item::Folder(_, children, ..) => children.iter().find(|child| child.name() == *name),
+            Item::File(..) => None,
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_back_to_an_ancestor_is_listed_as_a_cycle() {
+        let root = std::env::temp_dir().join(format!("folder-cli-symlink-test-{}", std::process::id()));
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::write(root.join("a/file.txt"), "").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("a/loop")).unwrap();
+
+        let followed = FolderStructureOptionsBuilder::default().follow_symlinks(true).build().unwrap();
+        let not_followed = FolderStructureOptionsBuilder::default().build().unwrap();
+        let followed = get_folder_structure(&root, &followed);
+        let not_followed = get_folder_structure(&root, &not_followed);
+        fs::remove_dir_all(&root).unwrap();
+
+        let followed = followed.unwrap();
+        let cycle = find(&followed, &["a", "loop"]).unwrap();
+        assert!(matches!(cycle, Item::Folder(_, children, ..) if children.is_empty()));
+        assert_eq!(cycle.metadata().link.as_deref(), Some("(cycle)"));
+
+        let not_followed = not_followed.unwrap();
+        let link = find(&not_followed, &["a", "loop"]).unwrap();
+        assert!(link.metadata().symlink);
+        assert_eq!(link.metadata().link.as_deref(), Some(root.to_str().unwrap()));
+    }
+
+    #[test]
+    fn build_regex_set_ignores_case_only_when_asked() {
+        let patterns = regexes(&["^readme\\.", "Makefile"]);
+        let insensitive = build_regex_set(&patterns, true).unwrap();
+        let sensitive = build_regex_set(&patterns, false).unwrap();
+
+        assert!(insensitive.is_match("README.md"));
+        assert!(insensitive.is_match("makefile"));
+        assert!(!sensitive.is_match("README.md"));
+        assert!(sensitive.is_match("readme.txt"));
+    }
+
+    #[test]
+    fn ignore_case_applies_to_include_and_exclude_patterns() {
+        let options = FolderStructureOptionsBuilder::default()
+            .include_by_filter(regexes(&["\\.jpg$"]))
+            .exclude_by_filter(regexes(&["^thumb"]))
+            .ignore_case(true)
+            .build()
+            .unwrap();
+
+        assert!(should_include_item("Photo.JPG", false, &options).is_ok());
+        assert_eq!(should_include_item("THUMB_1.jpg", false, &options), Err(ExclusionReason::ExcludedPattern("^thumb".to_string())));
+    }
+
+    #[test]
+    fn extension_filters_ignore_case_on_both_sides() {
+        let options = FolderStructureOptionsBuilder::default()
+            .include_extension_only(vec!["JPG".to_string(), "rs".to_string()])
+            .exclude_extension(vec!["Gen.RS".to_string()])
+            .build()
+            .unwrap();
+
+        assert_eq!(should_include_file("photo.jpg", &options), Ok(InclusionReason::IncludedExtension("JPG".to_string())));
+        assert_eq!(should_include_file("Photo.JpG", &options), Ok(InclusionReason::IncludedExtension("JPG".to_string())));
+        assert_eq!(should_include_file("MAIN.RS", &options), Ok(InclusionReason::IncludedExtension("rs".to_string())));
+        assert_eq!(should_include_file("parser.gen.rs", &options), Err(ExclusionReason::ExcludedExtension("Gen.RS".to_string())));
+        assert_eq!(should_include_file("notes.TXT", &options), Err(ExclusionReason::NotIncludedExtension));
+    }
+
+    #[test]
+    fn exclude_extension_refines_the_include_list() {
+        let options = FolderStructureOptionsBuilder::default()
+            .include_extension_only(vec!["rs".to_string()])
+            .exclude_extension(vec!["generated.rs".to_string()])
+            .build()
+            .unwrap();
+
+        assert_eq!(should_include_file("a.rs", &options), Ok(InclusionReason::IncludedExtension("rs".to_string())));
+        assert_eq!(
+            should_include_file("b.generated.rs", &options),
+            Err(ExclusionReason::ExcludedExtension("generated.rs".to_string()))
+        );
+    }
+
+    #[test]
+    fn validate_rejects_an_extension_both_included_and_excluded() {
+        let error = FolderStructureOptionsBuilder::default()
+            .include_extension_only(vec!["rs".to_string(), "md".to_string()])
+            .exclude_extension(vec![".RS".to_string()])
+            .build()
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().contains("Extension 'rs' cannot be both included and excluded"));
+    }
+
+    #[test]
+    fn validate_rejects_a_multi_dot_include_covered_by_a_shorter_exclude() {
+        let error = FolderStructureOptionsBuilder::default()
+            .include_extension_only(vec!["generated.rs".to_string()])
+            .exclude_extension(vec!["rs".to_string()])
+            .build()
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().contains("Extension 'generated.rs' is always excluded by 'rs'"));
+    }
+
+    #[test]
+    fn name_length_bounds_count_characters_not_bytes() {
+        // `日本.txt` is 6 characters but 10 bytes
+        let within = FolderStructureOptionsBuilder::default()
+            .min_name_length(Some(6))
+            .max_name_length(Some(6))
+            .build()
+            .unwrap();
+        let too_long = FolderStructureOptionsBuilder::default().max_name_length(Some(5)).build().unwrap();
+        let too_short = FolderStructureOptionsBuilder::default().min_name_length(Some(7)).build().unwrap();
+
+        assert_eq!(should_include_file("日本.txt", &within), Ok(InclusionReason::NoFilters));
+        assert_eq!(should_include_file("日本.txt", &too_long), Err(ExclusionReason::NameLength));
+        assert_eq!(should_include_file("日本.txt", &too_short), Err(ExclusionReason::NameLength));
+    }
+
+    #[test]
+    fn size_percent_shows_the_share_of_a_dominating_file() {
+        let source = memory_fs(&[("root/data.bin", 900), ("root/a.txt", 60), ("root/b.txt", 40)]);
+        let options = FolderStructureOptionsBuilder::default()
+            .show_size(true)
+            .show_size_percent(true)
+            .build()
+            .unwrap();
+
+        let root = get_folder_structure_from_source(Path::new("root"), &options, &source).unwrap();
+        assert_eq!(render_tree(&root, &options), concat!(
+            "root\n",
+            "    ├── a.txt (60 B) (6%)\n",
+            "    ├── b.txt (40 B) (4%)\n",
+            "    └── data.bin (900 B) (90%)\n",
+            "\n",
+            "0 directories, 3 files\n",
+        ));
+    }
+
+    #[test]
+    fn render_tree_scales_connectors_with_the_indent() {
+        let source = memory_fs(&[("root/a/b/c/deep.rs", 1), ("root/a/b/mid.rs", 1), ("root/top.rs", 1)]);
+        let render = |indent| {
+            let options = FolderStructureOptionsBuilder::default().indent(indent).build().unwrap();
+            let root = get_folder_structure_from_source(Path::new("root"), &options, &source).unwrap();
+            render_tree(&root, &options)
+        };
+
+        assert_eq!(render(2), concat!(
+            "root\n",
+            "  ├ a/\n",
+            "  │ └ b/\n",
+            "  │   ├ c/\n",
+            "  │   │ └ deep.rs\n",
+            "  │   └ mid.rs\n",
+            "  └ top.rs\n",
+            "\n",
+            "3 directories, 3 files\n",
+        ));
+        assert_eq!(render(8), concat!(
+            "root\n",
+            "        ├────── a/\n",
+            "        │       └────── b/\n",
+            "        │               ├────── c/\n",
+            "        │               │       └────── deep.rs\n",
+            "        │               └────── mid.rs\n",
+            "        └────── top.rs\n",
+            "\n",
+            "3 directories, 3 files\n",
+        ));
+    }
+
+    #[test]
+    fn on_progress_reports_every_interval_then_the_total() {
+        let files = (0..PROGRESS_INTERVAL + 5).map(|i| format!("root/{}.txt", i)).collect::<Vec<_>>();
+        let source = memory_fs(&files.iter().map(|file| (file.as_str(), 1)).collect::<Vec<_>>());
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let collector = Arc::clone(&reported);
+        let options = FolderStructureOptionsBuilder::default()
+            .on_progress(Some(Arc::new(move |progress| collector.lock().unwrap().push(progress))))
+            .build()
+            .unwrap();
+
+        get_folder_structure_from_source(Path::new("root"), &options, &source).unwrap();
+        assert_eq!(*reported.lock().unwrap(), [Progress::Scanned(PROGRESS_INTERVAL), Progress::Done(PROGRESS_INTERVAL + 5)]);
+    }
+
+    #[test]
+    fn validate_rejects_an_empty_age_window() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let error = FolderStructureOptionsBuilder::default()
+            .newer_than(Some(day))
+            .older_than(Some(7 * day))
+            .build()
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("older_than (604800s) must be shorter than newer_than (86400s)"));
+
+        assert!(FolderStructureOptionsBuilder::default().newer_than(Some(7 * day)).older_than(Some(day)).build().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_to_a_sibling_folder_is_followed() {
+        let root = std::env::temp_dir().join(format!("folder-cli-sibling-test-{}", std::process::id()));
+        fs::create_dir_all(root.join("src/lib")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("src/lib/m.rs"), "").unwrap();
+        std::os::unix::fs::symlink("../src/lib", root.join("docs/libref")).unwrap();
+
+        let render = |jobs| {
+            let options = FolderStructureOptionsBuilder::default().follow_symlinks(true).jobs(jobs).build().unwrap();
+            get_folder_structure(&root, &options).map(|item| render_tree(&item, &options))
+        };
+        let sequential = render(1);
+        let parallel = render(4);
+        fs::remove_dir_all(&root).unwrap();
+
+        let sequential = sequential.unwrap();
+        assert!(sequential.contains("libref/\n    │       └── m.rs"), "{}", sequential);
+        assert!(!sequential.contains("(cycle)"), "{}", sequential);
+        assert_eq!(sequential, parallel.unwrap());
+    }
+
+    #[test]
+    fn aligned_annotations_stay_in_line_when_names_are_shortened() {
+        let source = memory_fs(&[("root/a_very_long_file_name_here.bin", 3000), ("root/b.rs", 0)]);
+        for alignment in [Alignment::PerDirectory, Alignment::Global] {
+            let options = FolderStructureOptionsBuilder::default()
+                .show_size(true)
+                .alignment(Some(alignment))
+                .line_width(Some(30))
+                .show_summary(false)
+                .build()
+                .unwrap();
+            let root = get_folder_structure_from_source(Path::new("root"), &options, &source).unwrap();
+
+            assert_eq!(render_tree(&root, &options), concat!(
+                "root\n",
+                "    ├── a_very_long… (2.9 KiB)\n",
+                "    └── b.rs         (0 B)\n",
+            ));
+        }
+    }
+
+    /// Source whose directory reads always fail with the same error kind
+    struct FailingFs {
+        kind: io::ErrorKind,
+        reads: AtomicUsize,
+    }
+
+    impl DirSource for FailingFs {
+        fn read_dir(&self, _path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+            self.reads.fetch_add(1, atomic::Ordering::Relaxed);
+            Err(io::Error::from(self.kind))
+        }
+
+        fn is_dir(&self, _path: &Path) -> bool {
+            true
         }
+
+        fn exists(&self, _path: &Path) -> bool {
+            true
+        }
+
+        fn metadata(&self, _path: &Path) -> Option<EntryMetadata> {
+            None
+        }
+    }
+
+    #[test]
+    fn only_transient_read_errors_are_retried() {
+        for (kind, reads) in [(io::ErrorKind::TimedOut, 3), (io::ErrorKind::Interrupted, 3), (io::ErrorKind::PermissionDenied, 1), (io::ErrorKind::NotFound, 1)] {
+            let source = FailingFs { kind, reads: AtomicUsize::new(0) };
+            let error = read_dir_with_retry(Path::new("root"), 2, &source).unwrap_err();
+            assert_eq!(error.kind(), kind);
+            assert_eq!(source.reads.load(atomic::Ordering::Relaxed), reads, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn nested_gitignore_rules_take_precedence() {
+        // Listings come from memory, while `.gitignore` contents are read from disk
+        let root = std::env::temp_dir().join(format!("folder-cli-gitignore-{}", std::process::id()));
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join("logs/.gitignore"), "!keep.log\n").unwrap();
+
+        let mut source = MemoryFs::new();
+        for name in ["a.log", "main.rs", "logs/keep.log", "logs/drop.log"] {
+            source.add_file(root.join(name), EntryMetadata::default());
+        }
+        let options = FolderStructureOptionsBuilder::default()
+            .respect_gitignore(true)
+            .show_summary(false)
+            .build()
+            .unwrap();
+        let structure = get_folder_structure_from_source(&root, &options, &source);
+        fs::remove_dir_all(&root).unwrap();
+
+        let Item::Folder(_, items, ..) = structure.unwrap() else {
+            panic!("the root is a folder");
+        };
+        let names = items.iter().map(|item| item.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names, ["logs", "main.rs"]);
+        let Some(Item::Folder(_, logs, ..)) = items.iter().find(|item| item.name() == "logs") else {
+            panic!("logs is listed");
+        };
+        assert_eq!(logs.iter().map(|item| item.name()).collect::<Vec<_>>(), ["keep.log"]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn include_globs_are_anchored_at_the_root() {
+        let source = memory_fs(&[("root/src/a.rs", 1), ("root/lib/src/b.rs", 1)]);
+        let options = FolderStructureOptionsBuilder::default()
+            .include_globs(vec!["src/**".to_string()])
+            .show_summary(false)
+            .build()
+            .unwrap();
+        let root = get_folder_structure_from_source(Path::new("root"), &options, &source).unwrap();
+
+        assert_eq!(render_tree(&root, &options), concat!(
+            "root\n",
+            "    └── src/\n",
+            "        └── a.rs\n",
+        ));
+    }
+}
@@ -0,0 +1,237 @@
+//! Gitignore Pattern Matching
+//!
+//! Parses `.gitignore` files into regex-backed rules. Supports comments, negation
+//! (`!`), directory-only patterns (trailing `/`), anchored patterns (containing a
+//! `/`), and the `*`, `?`, `[...]` and `**` wildcards.
+
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+/// The rules of a single `.gitignore` file
+#[derive(Debug, Clone)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+/// One pattern line of a `.gitignore` file
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Pattern compiled to a regex over a name or a relative path
+    regex: Regex,
+
+    /// Whether the pattern re-includes matching entries (`!pattern`)
+    negated: bool,
+
+    /// Whether the pattern only applies to directories (`pattern/`)
+    dir_only: bool,
+
+    /// Whether the pattern is matched against the path relative to the
+    /// `.gitignore` directory rather than against the entry's name
+    anchored: bool,
+}
+
+impl Gitignore {
+    /// Reads and parses a `.gitignore` file
+    ///
+    /// # Arguments
+    /// * `path` - Path to the `.gitignore` file
+    ///
+    /// # Returns
+    /// * `Option<Gitignore>` - The parsed rules, `None` if the file cannot be read
+    pub fn from_file(path: &Path) -> Option<Gitignore> {
+        fs::read_to_string(path).ok().map(|contents| Gitignore::parse(&contents))
+    }
+
+    /// Parses the contents of a `.gitignore` file
+    ///
+    /// Lines that cannot be compiled are ignored, like git does with invalid patterns.
+    ///
+    /// # Arguments
+    /// * `contents` - The file contents
+    ///
+    /// # Returns
+    /// * `Gitignore` - The parsed rules, in file order
+    pub fn parse(contents: &str) -> Gitignore {
+        let rules = contents.lines().filter_map(parse_rule).collect();
+        Gitignore { rules }
+    }
+
+    /// Decides whether an entry is ignored by these rules
+    ///
+    /// The last matching rule wins, as in git.
+    ///
+    /// # Arguments
+    /// * `relative` - Path of the entry relative to the `.gitignore` directory, `/`-separated
+    /// * `is_dir` - Whether the entry is a directory
+    ///
+    /// # Returns
+    /// * `Option<bool>` - `Some(true)` if ignored, `Some(false)` if re-included, `None` if no rule matches
+    pub fn matched(&self, relative: &str, is_dir: bool) -> Option<bool> {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+
+        self.rules.iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| rule.regex.is_match(if rule.anchored { relative } else { name }))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Parses one line of a `.gitignore` file
+///
+/// # Arguments
+/// * `line` - The raw line
+///
+/// # Returns
+/// * `Option<Rule>` - The rule, `None` for blank lines, comments and invalid patterns
+fn parse_rule(line: &str) -> Option<Rule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+
+    // A slash anywhere but at the end ties the pattern to the `.gitignore` directory
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let regex = Regex::new(&glob_to_regex(pattern)).ok()?;
+    Some(Rule { regex, negated, dir_only, anchored })
+}
+
+/// Translates a gitignore-style glob into an anchored regex
+///
+/// `*` and `?` never cross a `/`, `**/` matches any number of leading folders
+/// and a trailing `**` matches everything below.
+///
+/// # Arguments
+/// * `pattern` - The glob, without negation, leading or trailing slashes
+///
+/// # Returns
+/// * `String` - The equivalent regex source
+pub fn glob_to_regex(pattern: &str) -> String {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut regex = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                let followed_by_slash = chars.get(i + 2) == Some(&'/');
+                if at_start && followed_by_slash {
+                    // `**/` matches zero or more leading directories
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    regex.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[i..].iter().position(|&c| c == ']') {
+                Some(end) if end > 1 => {
+                    let class = chars[i + 1..i + end].iter().collect::<String>();
+                    let class = match class.strip_prefix('!') {
+                        Some(rest) => format!("^{}", rest),
+                        None => class,
+                    };
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\"));
+                    regex.push(']');
+                    i += end + 1;
+                    continue;
+                }
+                _ => regex.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_last_matching_rule_wins() {
+        let gitignore = Gitignore::parse("*.log\n!keep.log\n");
+        assert_eq!(gitignore.matched("debug.log", false), Some(true));
+        assert_eq!(gitignore.matched("keep.log", false), Some(false));
+        assert_eq!(gitignore.matched("main.rs", false), None);
+
+        // A later rule ignores again what an earlier one re-included
+        let gitignore = Gitignore::parse("!keep.log\n*.log\n");
+        assert_eq!(gitignore.matched("keep.log", false), Some(true));
+    }
+
+    #[test]
+    fn anchored_patterns_match_the_relative_path_only() {
+        let gitignore = Gitignore::parse("/build\ndoc/*.txt\n*.tmp\n");
+        assert_eq!(gitignore.matched("build", true), Some(true));
+        assert_eq!(gitignore.matched("src/build", true), None);
+        assert_eq!(gitignore.matched("doc/notes.txt", false), Some(true));
+        assert_eq!(gitignore.matched("api/doc/notes.txt", false), None);
+
+        // Unanchored patterns match the name at any depth
+        assert_eq!(gitignore.matched("a/b/c.tmp", false), Some(true));
+    }
+
+    #[test]
+    fn directory_patterns_do_not_apply_to_files() {
+        let gitignore = Gitignore::parse("cache/\n");
+        assert_eq!(gitignore.matched("cache", true), Some(true));
+        assert_eq!(gitignore.matched("src/cache", true), Some(true));
+        assert_eq!(gitignore.matched("cache", false), None);
+    }
+
+    #[test]
+    fn double_stars_match_any_number_of_folders() {
+        let gitignore = Gitignore::parse("**/generated\nout/**\n");
+        assert_eq!(gitignore.matched("generated", true), Some(true));
+        assert_eq!(gitignore.matched("a/b/generated", true), Some(true));
+        assert_eq!(gitignore.matched("out/a/b.o", false), Some(true));
+        assert_eq!(gitignore.matched("out", true), None);
+        assert_eq!(gitignore.matched("src/out/b.o", false), None);
+    }
+
+    #[test]
+    fn negated_classes_exclude_their_characters() {
+        let gitignore = Gitignore::parse("[!a]*.rs\n");
+        assert_eq!(gitignore.matched("main.rs", false), Some(true));
+        assert_eq!(gitignore.matched("app.rs", false), None);
+    }
+
+    #[test]
+    fn escaped_hash_and_bang_are_literal() {
+        let gitignore = Gitignore::parse("# a comment\n\\#notes\n\\!important\n");
+        assert_eq!(gitignore.matched("#notes", false), Some(true));
+        assert_eq!(gitignore.matched("!important", false), Some(true));
+        assert_eq!(gitignore.matched("important", false), None);
+        assert_eq!(gitignore.matched("# a comment", false), None);
+    }
+}
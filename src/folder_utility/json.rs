@@ -0,0 +1,188 @@
+//! JSON Export of Directory Structures
+//!
+//! Serializes a built `Item` tree with serde for programmatic consumers, or
+//! streams entries as newline-delimited JSON while traversing.
+
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::folder_strucure::{is_displayed, walk, FolderStructureOptions, Item, StreamError, StreamSummary, WalkEvent};
+
+/// One node of the nested JSON tree
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TreeNode<'a> {
+    Folder { name: &'a str, children: Vec<TreeNode<'a>> },
+    File { name: &'a str },
+}
+
+/// One entry of the flat JSON array
+#[derive(Serialize)]
+struct FlatEntry<'a> {
+    depth: usize,
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: Option<u64>,
+}
+
+/// One line of the NDJSON stream
+#[derive(Serialize)]
+struct StreamEntry<'a> {
+    root: &'a str,
+    path: &'a str,
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    depth: usize,
+    size: Option<u64>,
+}
+
+/// Renders the folder structure as nested JSON objects
+///
+/// Folders become `{"type": "folder", "name", "children": [...]}` and files
+/// `{"type": "file", "name"}`, children keeping their sorted order. Items hidden
+/// by the filters are left out, as in the tree output.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options deciding which items are displayed and
+///   whether the document is indented
+///
+/// # Returns
+/// * `String` - The JSON document, `null` if the root itself is hidden
+pub fn to_json(root: &Item, options: &FolderStructureOptions) -> String {
+    let tree = to_tree_node(root, options);
+    serialize(&tree, options)
+}
+
+/// Serializes a value on one line, or indented with 2 spaces when `json_pretty` is set
+fn serialize<T: Serialize>(value: &T, options: &FolderStructureOptions) -> String {
+    let json = if options.json_pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) };
+    json.expect("JSON entries always serialize")
+}
+
+/// Converts an item and its displayed descendants into a JSON tree node
+fn to_tree_node<'a>(item: &'a Item, options: &FolderStructureOptions) -> Option<TreeNode<'a>> {
+    if !is_displayed(item, options) {
+        return None;
+    }
+
+    let node = match item {
+        Item::File(name, _) => TreeNode::File { name },
+        Item::Folder(name, items, ..) => TreeNode::Folder {
+            name,
+            children: items.iter().filter_map(|child| to_tree_node(child, options)).collect(),
+        },
+    };
+    Some(node)
+}
+
+/// Renders the folder structure as a flat JSON array
+///
+/// Each element is an object `{"depth", "name", "type", "size"}` where `type`
+/// is `"folder"` or `"file"` and `size` is in bytes (`null` if unreadable;
+/// folders hold the total of their files).
+///
+/// Entries are emitted in pre-order: the root first at depth 0, and every
+/// folder immediately followed by its children in sorted order. A consumer can
+/// rebuild the tree by keeping a stack of folders: an entry at depth `d` is a
+/// child of the last folder seen at depth `d - 1`.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options deciding which items are displayed and
+///   whether the array is indented
+///
+/// # Returns
+/// * `String` - The JSON array
+pub fn to_json_array(root: &Item, options: &FolderStructureOptions) -> String {
+    let mut entries = Vec::new();
+    collect_flat_entries(root, 0, options, &mut entries);
+    serialize(&entries, options)
+}
+
+/// Appends an item and its displayed descendants in pre-order
+fn collect_flat_entries<'a>(item: &'a Item, depth: usize, options: &FolderStructureOptions, entries: &mut Vec<FlatEntry<'a>>) {
+    if !is_displayed(item, options) {
+        return;
+    }
+
+    let kind = match item {
+        Item::File(..) => "file",
+        Item::Folder(..) => "folder",
+    };
+    entries.push(FlatEntry { depth, name: item.name(), kind, size: item.metadata().size });
+
+    if let Item::Folder(_, items, ..) = item {
+        for child in items {
+            collect_flat_entries(child, depth + 1, options, entries);
+        }
+    }
+}
+
+/// Traverses a path and writes one JSON object per entry and per line as entries are found
+///
+/// Each line is an object `{"root", "path", "name", "type", "depth", "size"}`:
+/// - `root` is the traversal root as given, telling apart the entries of several trees
+/// - `path` is relative to the root with `/` separators, `.` for the root itself
+/// - `type` is `"folder"` or `"file"`
+/// - `depth` is 0 for the root
+/// - `size` is in bytes, `null` for folders and for files whose size is unreadable
+///
+/// Entries come in pre-order, so every folder comes before its children, which
+/// are sorted as in the tree output. The traversal is `walk`'s, so the tree is
+/// never held in memory: folders are written before their contents are known,
+/// empty ones included, and carry no total size.
+///
+/// # Arguments
+/// * `path` - The starting path to traverse
+/// * `options` - Configuration options for filtering
+/// * `out` - The sink to write to
+///
+/// # Returns
+/// * `Result<StreamSummary, StreamError>` - What was written, or the error that stopped the
+///   output. A failing sink stops the traversal at once
+pub fn write_ndjson<W: Write>(path: &Path, options: &FolderStructureOptions, out: &mut W) -> Result<StreamSummary, StreamError> {
+    let root = path.display().to_string();
+    let mut file_count = 0;
+    let mut written = Ok(());
+
+    let entry_limit_reached = walk(path, options, |event| {
+        let (entry_path, item, depth) = match &event {
+            WalkEvent::EnterDir { path, depth, .. } => (*path, None, *depth),
+            WalkEvent::File { path, item, depth, .. } => (*path, Some(item), *depth),
+            WalkEvent::LeaveDir { .. } => return ControlFlow::Continue(()),
+        };
+
+        let relative = match entry_path.strip_prefix(path) {
+            Ok(relative) if depth > 0 => relative.to_string_lossy().replace('\\', "/"),
+            _ => ".".to_string(),
+        };
+        // A root given as `.` has no file name
+        let name = entry_path.file_name().map_or_else(|| ".".into(), |name| name.to_string_lossy());
+        let (kind, size) = match item {
+            Some(Item::File(_, metadata)) => ("file", metadata.size),
+            Some(Item::Folder(..)) | None => ("folder", None),
+        };
+        if kind == "file" {
+            file_count += 1;
+        }
+
+        let entry = StreamEntry { root: &root, path: &relative, name: &name, kind, depth, size };
+        let line = serde_json::to_string(&entry).expect("stream entries always serialize");
+        match writeln!(out, "{}", line) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(error) => {
+                written = Err(error);
+                ControlFlow::Break(())
+            }
+        }
+    })?;
+
+    written.map_err(StreamError::Write)?;
+    Ok(StreamSummary { files: file_count, entry_limit_reached })
+}
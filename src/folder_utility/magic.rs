@@ -0,0 +1,200 @@
+//! File Type Detection from Magic Numbers
+//!
+//! Infers a file's type from the signature in its first bytes and checks it
+//! against the declared extension, to catch mislabeled files.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Number of leading bytes read to recognize a signature
+///
+/// Most magic numbers fit in the first 16 bytes, but a PE header is found
+/// through an offset stored in the DOS stub and usually lies a few hundred bytes in.
+const HEADER_LEN: usize = 1024;
+
+/// A file type recognizable from its leading bytes
+struct Signature {
+    /// Display name of the type
+    name: &'static str,
+
+    /// Magic numbers with the byte offset each starts at, all of which must be present
+    magic: &'static [(usize, &'static [u8])],
+
+    /// Structural check run on the header once the magic numbers matched, for
+    /// magic numbers too short to be trusted alone
+    check: Option<fn(&[u8]) -> bool>,
+
+    /// Extensions that legitimately carry this type
+    extensions: &'static [&'static str],
+}
+
+impl Signature {
+    /// Checks whether the given leading bytes carry this signature
+    fn matches(&self, header: &[u8]) -> bool {
+        self.magic.iter().all(|&(offset, magic)| {
+            header.get(offset..offset + magic.len()).is_some_and(|bytes| bytes == magic)
+        }) && self.check.is_none_or(|check| check(header))
+    }
+}
+
+/// Known signatures, more specific entries first
+const SIGNATURES: &[Signature] = &[
+    Signature { name: "png", magic: &[(0, b"\x89PNG\r\n\x1a\n")], check: None, extensions: &["png"] },
+    Signature { name: "jpeg", magic: &[(0, b"\xff\xd8\xff")], check: None, extensions: &["jpg", "jpeg", "jpe", "jfif"] },
+    Signature { name: "gif", magic: &[(0, b"GIF8")], check: None, extensions: &["gif"] },
+    Signature { name: "webp", magic: &[(0, b"RIFF"), (8, b"WEBP")], check: None, extensions: &["webp"] },
+    Signature { name: "wav", magic: &[(0, b"RIFF"), (8, b"WAVE")], check: None, extensions: &["wav"] },
+    Signature { name: "avi", magic: &[(0, b"RIFF"), (8, b"AVI ")], check: None, extensions: &["avi"] },
+    Signature { name: "tiff", magic: &[(0, b"II*\x00")], check: None, extensions: &["tif", "tiff", "dng", "nef", "cr2"] },
+    Signature { name: "tiff", magic: &[(0, b"MM\x00*")], check: None, extensions: &["tif", "tiff", "dng", "nef", "cr2"] },
+    Signature { name: "ico", magic: &[(0, b"\x00\x00\x01\x00")], check: Some(is_ico), extensions: &["ico"] },
+    Signature { name: "pdf", magic: &[(0, b"%PDF-")], check: None, extensions: &["pdf"] },
+    Signature { name: "zip", magic: &[(0, b"PK\x03\x04")], check: None, extensions: &["zip", "jar", "war", "apk", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "whl", "nupkg", "vsix"] },
+    Signature { name: "gzip", magic: &[(0, b"\x1f\x8b")], check: None, extensions: &["gz", "tgz"] },
+    Signature { name: "bzip2", magic: &[(0, b"BZh")], check: Some(is_bzip2), extensions: &["bz2", "tbz2"] },
+    Signature { name: "xz", magic: &[(0, b"\xfd7zXZ\x00")], check: None, extensions: &["xz", "txz"] },
+    Signature { name: "7z", magic: &[(0, b"7z\xbc\xaf\x27\x1c")], check: None, extensions: &["7z"] },
+    Signature { name: "rar", magic: &[(0, b"Rar!\x1a\x07")], check: None, extensions: &["rar"] },
+    Signature { name: "zstd", magic: &[(0, b"\x28\xb5\x2f\xfd")], check: None, extensions: &["zst", "zstd"] },
+    Signature { name: "sqlite", magic: &[(0, b"SQLite format 3\x00")], check: None, extensions: &["sqlite", "sqlite3", "db"] },
+    Signature { name: "elf", magic: &[(0, b"\x7fELF")], check: None, extensions: &["so", "o", "elf", "bin", "out", "ko"] },
+    Signature { name: "pe", magic: &[(0, b"MZ")], check: Some(is_pe), extensions: &["exe", "dll", "sys", "efi", "scr"] },
+    Signature { name: "java class", magic: &[(0, b"\xca\xfe\xba\xbe")], check: None, extensions: &["class"] },
+    Signature { name: "wasm", magic: &[(0, b"\x00asm")], check: None, extensions: &["wasm"] },
+    Signature { name: "ogg", magic: &[(0, b"OggS")], check: None, extensions: &["ogg", "oga", "ogv", "opus"] },
+    Signature { name: "flac", magic: &[(0, b"fLaC")], check: None, extensions: &["flac"] },
+    Signature { name: "mp3", magic: &[(0, b"ID3")], check: Some(is_id3), extensions: &["mp3"] },
+    Signature { name: "mp4", magic: &[(4, b"ftyp")], check: None, extensions: &["mp4", "m4a", "m4v", "mov", "heic", "heif", "avif", "3gp"] },
+];
+
+/// Declared and detected types of a mislabeled file
+#[derive(Debug, PartialEq)]
+pub struct ExtensionMismatch {
+    /// Extension the file name declares
+    pub declared: String,
+
+    /// Type inferred from the file content
+    pub detected: &'static str,
+}
+
+/// Checks whether a file's content disagrees with its extension
+///
+/// Files without an extension, files that cannot be read and files whose
+/// content matches no known signature are never reported.
+///
+/// # Arguments
+/// * `path` - Path to the file
+///
+/// # Returns
+/// * `Option<ExtensionMismatch>` - The mismatch, if one was detected
+pub fn detect_extension_mismatch(path: &Path) -> Option<ExtensionMismatch> {
+    let declared = path.extension()?.to_string_lossy().to_lowercase();
+
+    let mut header = [0u8; HEADER_LEN];
+    let read = read_header(path, &mut header)?;
+    let signature = detect_signature(&header[..read])?;
+
+    if signature.extensions.contains(&declared.as_str()) {
+        return None;
+    }
+
+    Some(ExtensionMismatch { declared, detected: signature.name })
+}
+
+/// Reads up to `header.len()` leading bytes of a file
+///
+/// # Returns
+/// * `Option<usize>` - Number of bytes read, `None` if the file cannot be read
+fn read_header(path: &Path, header: &mut [u8]) -> Option<usize> {
+    let mut file = File::open(path).ok()?;
+    let mut read = 0;
+    while read < header.len() {
+        match file.read(&mut header[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(_) => return None,
+        }
+    }
+    Some(read)
+}
+
+/// Finds the first signature matching the given leading bytes
+fn detect_signature(header: &[u8]) -> Option<&'static Signature> {
+    SIGNATURES.iter().find(|signature| signature.matches(header))
+}
+
+/// Checks that a DOS stub points to a `PE\0\0` header, which `MZ` alone does not guarantee
+fn is_pe(header: &[u8]) -> bool {
+    let Some(&[a, b, c, d]) = header.get(0x3c..0x40) else {
+        return false;
+    };
+    let pe_offset = u32::from_le_bytes([a, b, c, d]) as usize;
+    header.get(pe_offset..pe_offset + 4).is_some_and(|bytes| bytes == b"PE\0\0")
+}
+
+/// Checks that an `ID3` tag carries a known version and a syncsafe size
+fn is_id3(header: &[u8]) -> bool {
+    match header.get(3..10) {
+        Some(&[major, revision, _flags, ref size @ ..]) => {
+            matches!(major, 2..=4) && revision != 0xff && size.iter().all(|&byte| byte < 0x80)
+        }
+        _ => false,
+    }
+}
+
+/// Checks that `BZh` is followed by a block size and the magic of a block or of the end of stream
+fn is_bzip2(header: &[u8]) -> bool {
+    header.get(3).is_some_and(|level| (b'1'..=b'9').contains(level))
+        && header.get(4..10).is_some_and(|magic| magic == b"\x31\x41\x59\x26\x53\x59" || magic == b"\x17\x72\x45\x38\x50\x90")
+}
+
+/// Checks that an icon header lists at least one image whose directory entry is well formed
+fn is_ico(header: &[u8]) -> bool {
+    let Some(&[count_low, count_high, _width, _height, _colors, reserved, planes_low, planes_high]) = header.get(4..12) else {
+        return false;
+    };
+    u16::from_le_bytes([count_low, count_high]) > 0 && reserved == 0 && u16::from_le_bytes([planes_low, planes_high]) <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Detects the type name of the given leading bytes
+    fn detect(header: &[u8]) -> Option<&'static str> {
+        detect_signature(header).map(|signature| signature.name)
+    }
+
+    #[test]
+    fn riff_subtypes_require_the_riff_container() {
+        assert_eq!(detect(b"RIFF\x24\x00\x00\x00WAVEfmt "), Some("wav"));
+        assert_eq!(detect(b"RIFF\x24\x00\x00\x00WEBPVP8 "), Some("webp"));
+        assert_eq!(detect(b"RIFF\x24\x00\x00\x00AVI LIST"), Some("avi"));
+        assert_eq!(detect(b"notes: WAVE form"), None);
+    }
+
+    #[test]
+    fn pe_requires_the_header_the_dos_stub_points_to() {
+        let mut header = vec![0u8; 0x90];
+        header[..2].copy_from_slice(b"MZ");
+        header[0x3c] = 0x80;
+        assert_eq!(detect(&header), None);
+
+        header[0x80..0x84].copy_from_slice(b"PE\0\0");
+        assert_eq!(detect(&header), Some("pe"));
+        assert_eq!(detect(b"MZ is a plain text line"), None);
+    }
+
+    #[test]
+    fn short_magic_numbers_need_a_valid_structure() {
+        assert_eq!(detect(b"ID3\x04\x00\x00\x00\x00\x1f\x76"), Some("mp3"));
+        assert_eq!(detect(b"ID3 tags are great"), None);
+
+        assert_eq!(detect(b"BZh91AY&SY\x00\x00"), Some("bzip2"));
+        assert_eq!(detect(b"BZh is not bzip2"), None);
+
+        assert_eq!(detect(b"\x00\x00\x01\x00\x01\x00\x10\x10\x00\x00\x01\x00"), Some("ico"));
+        assert_eq!(detect(b"\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00"), None);
+    }
+}
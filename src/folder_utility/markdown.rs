@@ -0,0 +1,63 @@
+//! Markdown Rendering of Directory Structures
+//!
+//! Renders an `Item` tree as a nested Markdown bullet list, suitable for
+//! pasting into documentation. Folders are bold and end with `/`.
+
+use super::folder_strucure::{format_annotations, is_displayed, FolderStructureOptions, Item};
+
+/// Renders the folder structure as a nested Markdown list
+///
+/// Each entry is a `- name` bullet indented by two spaces per level, folders
+/// being written `- **name/**`. Annotations follow the name as in the tree output.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options for display
+///
+/// # Returns
+/// * `String` - The Markdown list, one line per entry
+pub fn to_markdown(root: &Item, options: &FolderStructureOptions) -> String {
+    let mut markdown = String::new();
+    write_entries(root, 0, None, options, &mut markdown);
+    markdown
+}
+
+/// Appends the bullets for an item and its displayed descendants in pre-order
+///
+/// # Arguments
+/// * `item` - The item to render
+/// * `depth` - Depth of the item, 0 for the root
+/// * `parent_size` - Total size of the parent folder, `None` for the root
+/// * `options` - Configuration options for display
+/// * `markdown` - The list rendered so far
+fn write_entries(item: &Item, depth: usize, parent_size: Option<u64>, options: &FolderStructureOptions, markdown: &mut String) {
+    if !is_displayed(item, options) {
+        return;
+    }
+
+    let indent = "  ".repeat(depth);
+    let annotation = escape_markdown(&format_annotations(item, parent_size, options));
+    match item {
+        Item::File(name, _) => {
+            markdown.push_str(&format!("{}- {}{}\n", indent, escape_markdown(name), annotation));
+        }
+        Item::Folder(name, items, _, metadata) => {
+            markdown.push_str(&format!("{}- **{}/**{}\n", indent, escape_markdown(name), annotation));
+            for child in items {
+                write_entries(child, depth + 1, metadata.size, options, markdown);
+            }
+        }
+    }
+}
+
+/// Escapes the characters Markdown would interpret as formatting
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
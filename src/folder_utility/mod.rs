@@ -1 +1,18 @@
-pub mod folder_strucure;
\ No newline at end of file
+pub mod bundles;
+pub mod csv;
+pub mod diff;
+pub mod dot;
+pub mod dupes;
+pub mod folder_strucure;
+pub mod gitignore;
+pub mod json;
+pub mod magic;
+pub mod markdown;
+pub mod prometheus;
+pub mod search;
+pub mod summary;
+pub mod svg;
+pub mod terminal;
+pub mod units;
+pub mod users;
+pub mod yaml;
\ No newline at end of file
@@ -0,0 +1,40 @@
+//! Prometheus Text-Format Export
+//!
+//! Emits the summary statistics of a directory structure as Prometheus gauges,
+//! labeled with the root path, so directory growth can be scraped and monitored.
+
+use super::summary::Summary;
+
+/// Renders summary statistics in the Prometheus text exposition format
+///
+/// # Arguments
+/// * `summary` - The statistics to export
+/// * `root_path` - The traversal root, used as the `root` label
+///
+/// # Returns
+/// * `String` - The metrics, one `# HELP`/`# TYPE` header and sample per metric
+pub fn to_prometheus(summary: &Summary, root_path: &str) -> String {
+    let label = format!("{{root=\"{}\"}}", escape_label_value(root_path));
+    let metrics = [
+        ("fs_tools_files", "Number of files in the tree", summary.file_count as u64),
+        ("fs_tools_directories", "Number of directories in the tree, excluding the root", summary.dir_count as u64),
+        ("fs_tools_size_bytes", "Total size of the files in the tree in bytes", summary.total_size),
+        ("fs_tools_max_depth", "Depth of the deepest entry, the root being at depth 0", summary.max_depth as u64),
+    ];
+
+    let mut output = String::new();
+    for (name, help, value) in metrics {
+        output.push_str(&format!("# HELP {} {}\n", name, help));
+        output.push_str(&format!("# TYPE {} gauge\n", name));
+        output.push_str(&format!("{}{} {}\n", name, label, value));
+    }
+    output
+}
+
+/// Escapes a label value as required by the text exposition format
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
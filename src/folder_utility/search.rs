@@ -0,0 +1,39 @@
+//! File Search Over Directory Structures
+//!
+//! Lists the files kept in a built `Item` tree as flat relative paths, one per
+//! line, so the result can be piped to tools such as `xargs`. Name matching is
+//! done while building the tree through the `include_by_filter` option.
+
+use super::folder_strucure::{is_displayed, FolderStructureOptions, Item};
+
+/// Lists the paths of the displayed files, relative to the root
+///
+/// Paths use `/` separators and come in the tree's pre-order, so the sort
+/// options apply within each folder.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options deciding which items are displayed
+///
+/// # Returns
+/// * `Vec<String>` - The relative path of every displayed file
+pub fn file_paths(root: &Item, options: &FolderStructureOptions) -> Vec<String> {
+    let mut paths = Vec::new();
+    if let Item::Folder(_, items, ..) = root {
+        if is_displayed(root, options) {
+            collect_paths(items, "", options, &mut paths);
+        }
+    }
+    paths
+}
+
+/// Appends the paths of the displayed files among items and their descendants
+fn collect_paths(items: &[Item], parent: &str, options: &FolderStructureOptions, paths: &mut Vec<String>) {
+    for item in items.iter().filter(|item| is_displayed(item, options)) {
+        let path = format!("{}{}", parent, item.name());
+        match item {
+            Item::File(..) => paths.push(path),
+            Item::Folder(_, children, ..) => collect_paths(children, &format!("{}/", path), options, paths),
+        }
+    }
+}
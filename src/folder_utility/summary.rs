@@ -0,0 +1,95 @@
+//! Summary Statistics of Directory Structures
+//!
+//! Computes totals over a built `Item` tree. Counts reflect the filtered tree,
+//! so they match what the printers display rather than the raw directory contents.
+
+use std::collections::HashMap;
+
+use super::folder_strucure::{extension_key, is_displayed, FolderStructureOptions, Item};
+
+/// Totals computed over a folder structure
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Summary {
+    /// Number of displayed files
+    pub file_count: usize,
+
+    /// Number of displayed folders, not counting the root
+    pub dir_count: usize,
+
+    /// Total size in bytes of the displayed files
+    pub total_size: u64,
+
+    /// Depth of the deepest displayed entry, the root being at depth 0
+    pub max_depth: usize,
+}
+
+impl Summary {
+    /// Computes the summary of a folder structure
+    ///
+    /// # Arguments
+    /// * `root` - The root item of the structure
+    /// * `options` - Configuration options deciding which items are displayed
+    ///
+    /// # Returns
+    /// * `Summary` - The computed totals
+    pub fn from_item(root: &Item, options: &FolderStructureOptions) -> Summary {
+        let mut summary = Summary::default();
+        if is_displayed(root, options) {
+            summary.add(root, 0, options);
+        }
+        summary
+    }
+
+    /// Adds an item and its displayed descendants to the totals
+    fn add(&mut self, item: &Item, depth: usize, options: &FolderStructureOptions) {
+        self.max_depth = self.max_depth.max(depth);
+
+        match item {
+            Item::File(_, metadata) => {
+                self.file_count += 1;
+                self.total_size = self.total_size.saturating_add(metadata.size.unwrap_or(0));
+            }
+            Item::Folder(_, items, ..) => {
+                if depth > 0 {
+                    self.dir_count += 1;
+                }
+                for child in items.iter().filter(|child| is_displayed(child, options)) {
+                    self.add(child, depth + 1, options);
+                }
+            }
+        }
+    }
+}
+
+/// Counts the displayed files of a folder structure by extension
+///
+/// Extensions are compared case-insensitively, files without one being counted as `(none)`.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options deciding which items are displayed
+///
+/// # Returns
+/// * `Vec<(String, usize)>` - Extensions and their file counts, most frequent first, ties by name
+pub fn count_by_extension(root: &Item, options: &FolderStructureOptions) -> Vec<(String, usize)> {
+    let mut counts = HashMap::new();
+    if is_displayed(root, options) {
+        tally_extensions(root, options, &mut counts);
+    }
+
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(ext_a, count_a), (ext_b, count_b)| count_b.cmp(count_a).then_with(|| ext_a.cmp(ext_b)));
+    counts
+}
+
+/// Adds the displayed files of an item to the per-extension counts
+fn tally_extensions(item: &Item, options: &FolderStructureOptions, counts: &mut HashMap<String, usize>) {
+    match item {
+        Item::File(name, _) => *counts.entry(extension_key(name)).or_insert(0) += 1,
+        Item::Folder(_, items, ..) => {
+            for child in items.iter().filter(|child| is_displayed(child, options)) {
+                tally_extensions(child, options, counts);
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! SVG Rendering of Directory Structures
+//!
+//! Renders an `Item` tree as a self-contained scalable vector graphic: one
+//! text node per row, indented by depth, with connector lines drawn from each
+//! folder to its children. Styles are embedded so the file renders anywhere.
+
+use super::folder_strucure::{format_annotations, is_displayed, FolderStructureOptions, Item};
+
+/// Height of a single row in pixels
+const ROW_HEIGHT: usize = 22;
+
+/// Horizontal offset added per depth level in pixels
+const INDENT: usize = 24;
+
+/// Space around the drawing in pixels
+const MARGIN: usize = 12;
+
+/// Approximate width of one monospace character in pixels
+const CHAR_WIDTH: usize = 8;
+
+/// A laid-out row of the graphic
+struct SvgRow {
+    depth: usize,
+    label: String,
+    is_folder: bool,
+    parent: Option<usize>,
+}
+
+/// Renders the folder structure as an SVG document
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options for display
+///
+/// # Returns
+/// * `String` - The SVG document
+pub fn to_svg(root: &Item, options: &FolderStructureOptions) -> String {
+    let mut rows = Vec::new();
+    layout_rows(root, 0, None, None, options, &mut rows);
+
+    let width = rows.iter()
+        .map(|row| row_x(row.depth) + row.label.chars().count() * CHAR_WIDTH)
+        .max()
+        .unwrap_or(0) + MARGIN;
+    let height = rows.len() * ROW_HEIGHT + 2 * MARGIN;
+
+    let mut svg = String::new();
+    svg.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = width,
+        h = height
+    ));
+    svg.push_str("  <style>\n");
+    svg.push_str("    text { font-family: monospace; font-size: 13px; dominant-baseline: middle; }\n");
+    svg.push_str("    .folder { fill: #1f4fa0; font-weight: bold; }\n");
+    svg.push_str("    .file { fill: #222222; }\n");
+    svg.push_str("    .connector { stroke: #999999; stroke-width: 1; fill: none; }\n");
+    svg.push_str("  </style>\n");
+
+    // Connectors first so the text is drawn on top of them
+    for (index, row) in rows.iter().enumerate() {
+        if let Some(parent) = row.parent {
+            svg.push_str(&format!(
+                "  <path class=\"connector\" d=\"M{} {} V{} H{}\"/>\n",
+                row_x(rows[parent].depth) + CHAR_WIDTH / 2,
+                row_y(parent) + ROW_HEIGHT / 2 - 4,
+                row_y(index),
+                row_x(row.depth) - 4
+            ));
+        }
+    }
+
+    for (index, row) in rows.iter().enumerate() {
+        svg.push_str(&format!(
+            "  <text class=\"{}\" x=\"{}\" y=\"{}\">{}</text>\n",
+            if row.is_folder { "folder" } else { "file" },
+            row_x(row.depth),
+            row_y(index),
+            escape_xml(&row.label)
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Appends the rows for an item and its displayed descendants in pre-order
+///
+/// # Arguments
+/// * `item` - The item to lay out
+/// * `depth` - Depth of the item, 0 for the root
+/// * `parent` - Row index of the parent, `None` for the root
+/// * `parent_size` - Total size of the parent folder, `None` for the root
+/// * `options` - Configuration options for display
+/// * `rows` - The rows laid out so far
+fn layout_rows(
+    item: &Item,
+    depth: usize,
+    parent: Option<usize>,
+    parent_size: Option<u64>,
+    options: &FolderStructureOptions,
+    rows: &mut Vec<SvgRow>,
+) {
+    if !is_displayed(item, options) {
+        return;
+    }
+
+    let annotation = format_annotations(item, parent_size, options);
+    let index = rows.len();
+    match item {
+        Item::File(name, _) => rows.push(SvgRow {
+            depth,
+            label: format!("{}{}", name, annotation),
+            is_folder: false,
+            parent,
+        }),
+        Item::Folder(name, items, _, metadata) => {
+            let label = if parent.is_none() {
+                format!("{}{}", name, annotation)
+            } else {
+                format!("{}/{}", name, annotation)
+            };
+            rows.push(SvgRow { depth, label, is_folder: true, parent });
+
+            for child in items {
+                layout_rows(child, depth + 1, Some(index), metadata.size, options, rows);
+            }
+        }
+    }
+}
+
+/// Horizontal position of the text for a given depth
+fn row_x(depth: usize) -> usize {
+    MARGIN + depth * INDENT
+}
+
+/// Vertical position of the middle of a given row
+fn row_y(index: usize) -> usize {
+    MARGIN + index * ROW_HEIGHT + ROW_HEIGHT / 2
+}
+
+/// Escapes the characters that are special in XML text
+///
+/// # Arguments
+/// * `text` - The text to escape
+///
+/// # Returns
+/// * `String` - The escaped text
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,76 @@
+//! Terminal Size
+//!
+//! Detects the width of the terminal stdout is attached to, so long names can be
+//! shortened to fit it.
+//!
+//! The size is read with `ioctl(TIOCGWINSZ)` through a hand-written binding
+//! rather than the `libc` or `terminal_size` crates: this single call does not
+//! justify a dependency. The binding is only enabled for the platforms whose
+//! request number and `struct winsize` layout are known here.
+
+use std::env;
+
+/// Width assumed for a terminal whose size cannot be detected
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Reads the width of the terminal stdout is attached to
+///
+/// The terminal is asked directly, since interactive shells set `COLUMNS`
+/// without exporting it. An exported `COLUMNS` is the fallback for platforms
+/// where the terminal cannot be queried, then `DEFAULT_WIDTH`.
+///
+/// Only meaningful when stdout is a terminal.
+///
+/// # Returns
+/// * `usize` - The width in columns
+pub fn terminal_width() -> usize {
+    tty_columns()
+        .or_else(|| {
+            env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.trim().parse().ok())
+                .filter(|&columns: &usize| columns > 0)
+        })
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Queries the number of columns of the terminal on stdout with `ioctl(TIOCGWINSZ)`
+#[cfg(any(
+    target_os = "macos",
+    all(target_os = "linux", any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64", target_arch = "riscv64"))
+))]
+fn tty_columns() -> Option<usize> {
+    use std::ffi::{c_int, c_ulong};
+
+    /// Mirror of the C `struct winsize`
+    #[repr(C)]
+    struct WinSize {
+        rows: u16,
+        columns: u16,
+        x_pixels: u16,
+        y_pixels: u16,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    #[cfg(target_os = "linux")]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(target_os = "macos")]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+
+    let mut size = WinSize { rows: 0, columns: 0, x_pixels: 0, y_pixels: 0 };
+    // SAFETY: TIOCGWINSZ only fills in the `winsize` struct it is given, which `WinSize` mirrors
+    let result = unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut WinSize) };
+    (result == 0 && size.columns > 0).then_some(usize::from(size.columns))
+}
+
+/// The terminal size request is only wired up for the platforms above
+#[cfg(not(any(
+    target_os = "macos",
+    all(target_os = "linux", any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64", target_arch = "riscv64"))
+)))]
+fn tty_columns() -> Option<usize> {
+    None
+}
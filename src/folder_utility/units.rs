@@ -0,0 +1,170 @@
+//! Parsing and formatting helpers for human-friendly units
+//!
+//! Command-line arguments such as `7d` or `10M` are parsed here, and captured metadata
+//! (timestamps) is formatted back into a readable form for display.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parses a human-readable duration such as `30s`, `15m`, `2h`, `7d` or `1w`
+///
+/// # Arguments
+/// * `value` - The duration string, a positive integer followed by a unit
+///
+/// # Returns
+/// * `Result<Duration, String>` - The parsed duration or a description of the problem
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split_at = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("Missing unit in duration '{}' (expected s, m, h, d or w)", value))?;
+    let (amount, unit) = value.split_at(split_at);
+
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid duration amount in '{}'", value))?;
+
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Unknown duration unit '{}' (expected s, m, h, d or w)", unit)),
+    };
+
+    Ok(Duration::from_secs(amount.saturating_mul(seconds_per_unit)))
+}
+
+/// Parses a human-readable size such as `500`, `500K`, `10M` or `1.5GiB` into bytes
+///
+/// Suffixes are case-insensitive and use binary multiples (`K` = 1024 bytes).
+///
+/// # Arguments
+/// * `value` - The size string, a number optionally followed by a unit
+///
+/// # Returns
+/// * `Result<u64, String>` - The size in bytes or a description of the problem
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split_at = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split_at);
+
+    let amount: f64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid size amount in '{}'", value))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(format!("Unknown size unit '{}' (expected B, K, M, G or T)", unit)),
+    };
+
+    Ok((amount * multiplier as f64) as u64)
+}
+
+/// Formats a size in bytes with binary units, e.g. `512 B`, `1.2 KiB` or `3.4 MiB`
+///
+/// # Arguments
+/// * `bytes` - The size to format
+///
+/// # Returns
+/// * `String` - The size with one decimal above 1 KiB
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Formats a timestamp as `YYYY-MM-DD HH:MM` in UTC
+///
+/// # Arguments
+/// * `time` - The timestamp to format
+///
+/// # Returns
+/// * `String` - The formatted timestamp
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    };
+
+    let days = seconds.div_euclid(86_400);
+    let seconds_of_day = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        (seconds_of_day % 3_600) / 60
+    )
+}
+
+/// Formats how long ago a timestamp was, e.g. `just now`, `5 minutes ago` or `3 days ago`
+///
+/// Months count 30 days and years 365, and timestamps later than `now`
+/// are shown as `in the future`.
+///
+/// # Arguments
+/// * `time` - The timestamp to format
+/// * `now` - The current time
+///
+/// # Returns
+/// * `String` - The elapsed time in its largest whole unit
+pub fn format_relative_time(time: SystemTime, now: SystemTime) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("week", 7 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+    ];
+
+    let Ok(elapsed) = now.duration_since(time) else {
+        return "in the future".to_string();
+    };
+
+    let seconds = elapsed.as_secs();
+    match UNITS.iter().find(|(_, length)| seconds >= *length) {
+        Some((unit, length)) => {
+            let amount = seconds / length;
+            format!("{} {}{} ago", amount, unit, if amount == 1 { "" } else { "s" })
+        }
+        None => "just now".to_string(),
+    }
+}
+
+/// Converts a number of days since the Unix epoch into a (year, month, day) date
+///
+/// Based on Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
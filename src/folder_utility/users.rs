@@ -0,0 +1,111 @@
+//! User Account Lookup
+//!
+//! Resolves user names to numeric uids so ownership filters can compare them
+//! against file metadata. Only meaningful on Unix systems.
+
+/// Resolves a user name or numeric uid to a uid
+///
+/// Numeric values are accepted as-is; names are looked up in the system's user
+/// database, which also covers NSS, LDAP and macOS Directory Services accounts
+/// on Linux and macOS. Other systems only see the users of `/etc/passwd`.
+///
+/// # Arguments
+/// * `user` - The user name or numeric uid
+///
+/// # Returns
+/// * `Result<u32, String>` - The uid or a description of the problem
+pub fn resolve_uid(user: &str) -> Result<u32, String> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+
+    lookup_uid(user)?.ok_or_else(|| format!("Unknown user '{}'", user))
+}
+
+/// Looks a user name up with `getpwnam_r`
+///
+/// The binding is written by hand rather than pulled from the `libc` crate, as
+/// this single call does not justify a dependency.
+///
+/// # Arguments
+/// * `user` - The user name
+///
+/// # Returns
+/// * `Result<Option<u32>, String>` - The uid, `None` if the user does not exist, or the lookup error
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn lookup_uid(user: &str) -> Result<Option<u32>, String> {
+    use std::ffi::{c_char, c_int, CString};
+    use std::ptr;
+
+    /// Mirror of the C `struct passwd`
+    ///
+    /// Only the leading fields, laid out alike on Linux and macOS, are read. The
+    /// trailing ones differ between the two and are covered by padding large enough for either.
+    #[repr(C)]
+    struct Passwd {
+        name: *mut c_char,
+        password: *mut c_char,
+        uid: u32,
+        gid: u32,
+        rest: [*mut c_char; 8],
+    }
+
+    extern "C" {
+        fn getpwnam_r(name: *const c_char, pwd: *mut Passwd, buf: *mut c_char, buflen: usize, result: *mut *mut Passwd) -> c_int;
+    }
+
+    /// `errno` value reported when the string buffer is too small, on both platforms
+    const ERANGE: c_int = 34;
+
+    let Ok(name) = CString::new(user) else {
+        return Ok(None);
+    };
+    let mut buffer = vec![0 as c_char; 1024];
+    loop {
+        let mut entry = Passwd { name: ptr::null_mut(), password: ptr::null_mut(), uid: 0, gid: 0, rest: [ptr::null_mut(); 8] };
+        let mut result = ptr::null_mut();
+        // SAFETY: every pointer refers to a live value, the buffer length is its actual length,
+        // and `Passwd` is at least as large as the platform's `struct passwd`
+        let status = unsafe { getpwnam_r(name.as_ptr(), &mut entry, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+        match status {
+            0 => return Ok((!result.is_null()).then_some(entry.uid)),
+            ERANGE if buffer.len() < 1 << 20 => buffer.resize(buffer.len() * 2, 0),
+            _ => return Err(format!("Cannot resolve user '{}': {}", user, std::io::Error::from_raw_os_error(status))),
+        }
+    }
+}
+
+/// Looks a user name up in `/etc/passwd`
+///
+/// # Arguments
+/// * `user` - The user name
+///
+/// # Returns
+/// * `Result<Option<u32>, String>` - The uid, `None` if the user is not listed, or the read error
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn lookup_uid(user: &str) -> Result<Option<u32>, String> {
+    let passwd = std::fs::read_to_string("/etc/passwd")
+        .map_err(|e| format!("Cannot read /etc/passwd to resolve user '{}': {}", user, e))?;
+
+    Ok(passwd
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .find_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?;
+            (name == user).then(|| uid.parse().ok()).flatten()
+        }))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_names_and_numeric_uids() {
+        assert_eq!(resolve_uid("root"), Ok(0));
+        assert_eq!(resolve_uid("1234"), Ok(1234));
+        assert_eq!(resolve_uid("no-such-user-here"), Err("Unknown user 'no-such-user-here'".to_string()));
+    }
+}
@@ -0,0 +1,183 @@
+//! YAML Export of Directory Structures
+//!
+//! Renders an `Item` tree as a YAML document without pulling in a YAML
+//! serializer: the output only uses block sequences, single-key mappings and
+//! scalars, which are simple enough to write by hand.
+
+use super::folder_strucure::{is_displayed, FolderStructureOptions, Item};
+
+/// Renders the folder structure as a YAML document
+///
+/// Every folder is a single-key mapping from its name to the sequence of its
+/// children, and every file is a plain scalar entry of that sequence, so the
+/// two are told apart by their YAML type:
+///
+/// ```yaml
+/// project:
+///   - src:
+///       - main.rs
+///   - empty: []
+///   - Cargo.toml
+/// ```
+///
+/// Names that YAML would read as anything but a string (numbers, booleans,
+/// `null`, names with `:` or `#`, leading indicators...) are double-quoted.
+///
+/// # Arguments
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options deciding which items are displayed
+///
+/// # Returns
+/// * `String` - The YAML document, empty if the root itself is hidden
+pub fn to_yaml(root: &Item, options: &FolderStructureOptions) -> String {
+    let mut yaml = String::new();
+    if !is_displayed(root, options) {
+        return yaml;
+    }
+
+    match root {
+        Item::File(name, _) => yaml.push_str(&format!("{}\n", quote_yaml(name))),
+        Item::Folder(name, items, ..) => write_folder(name, items, "", 2, options, &mut yaml),
+    }
+    yaml
+}
+
+/// Appends a folder mapping and its displayed children
+///
+/// # Arguments
+/// * `name` - Name of the folder
+/// * `items` - Children of the folder
+/// * `lead` - Text written before the key, the indentation and `- ` for sequence entries
+/// * `indent` - Indentation of the children sequence
+/// * `options` - Configuration options deciding which items are displayed
+/// * `yaml` - The document rendered so far
+fn write_folder(name: &str, items: &[Item], lead: &str, indent: usize, options: &FolderStructureOptions, yaml: &mut String) {
+    let children = items.iter().filter(|item| is_displayed(item, options)).collect::<Vec<_>>();
+    if children.is_empty() {
+        yaml.push_str(&format!("{}{}: []\n", lead, quote_yaml(name)));
+        return;
+    }
+
+    yaml.push_str(&format!("{}{}:\n", lead, quote_yaml(name)));
+    let child_lead = format!("{}- ", " ".repeat(indent));
+    for child in children {
+        match child {
+            Item::File(name, _) => yaml.push_str(&format!("{}{}\n", child_lead, quote_yaml(name))),
+            Item::Folder(name, items, ..) => write_folder(name, items, &child_lead, indent + 4, options, yaml),
+        }
+    }
+}
+
+/// Quotes a name when YAML would not read it back as the same plain string
+fn quote_yaml(name: &str) -> String {
+    if is_plain_safe(name) {
+        return name.to_string();
+    }
+
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('"');
+    for c in name.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Whether a name can be written as a plain scalar and still be read as that string
+fn is_plain_safe(name: &str) -> bool {
+    const RESERVED: [&str; 12] = ["true", "false", "null", "yes", "no", "on", "off", "y", "n", "~", ".inf", ".nan"];
+
+    let Some(first) = name.chars().next() else {
+        return false;
+    };
+
+    !RESERVED.contains(&name.to_lowercase().as_str())
+        && name.parse::<f64>().is_err()
+        && !is_yaml_number(name)
+        && !name.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@` ".contains(c))
+        && !name.ends_with([' ', ':'])
+        && !name.contains(": ")
+        && !name.contains(" #")
+        && !name.chars().any(|c| c.is_control() || c == '\u{feff}')
+        && (first != '.' || name.len() > 1)
+}
+
+/// Whether a name reads as an integer or a float in YAML 1.1 or 1.2, in any base or notation
+///
+/// Covers `0x`, `0o` and `0b` prefixes, `_` digit separators, exponents, `.inf` and
+/// `.nan`, and the base 60 numbers of YAML 1.1 such as `1:30`. Legacy octals like
+/// `017` are decimal digits, so they are covered too.
+fn is_yaml_number(name: &str) -> bool {
+    let unsigned = name.strip_prefix(['+', '-']).unwrap_or(name);
+    let digits = |value: &str, radix: u32| {
+        value.chars().all(|c| c == '_' || c.is_digit(radix)) && value.chars().any(|c| c.is_digit(radix))
+    };
+
+    if let Some(hex) = unsigned.strip_prefix("0x") {
+        return digits(hex, 16);
+    }
+    if let Some(octal) = unsigned.strip_prefix("0o") {
+        return digits(octal, 8);
+    }
+    if let Some(binary) = unsigned.strip_prefix("0b") {
+        return digits(binary, 2);
+    }
+    if matches!(unsigned.to_lowercase().as_str(), ".inf" | ".nan") {
+        return true;
+    }
+
+    // Base 60, e.g. `190:20:30` or `1:30.5`, each part after the first being below 60
+    if let Some((first, rest)) = unsigned.split_once(':') {
+        let parts = rest.split(':').collect::<Vec<_>>();
+        return digits(first, 10) && parts.iter().enumerate().all(|(index, part)| {
+            let whole = match part.split_once('.') {
+                Some((whole, fraction)) if index == parts.len() - 1 => {
+                    fraction.chars().all(|c| c == '_' || c.is_ascii_digit()).then_some(whole)
+                }
+                Some(_) => None,
+                None => Some(*part),
+            };
+            whole.is_some_and(|whole| (1..=2).contains(&whole.len()) && whole.parse::<u8>().is_ok_and(|value| value < 60))
+        });
+    }
+
+    // Decimal, with an optional fraction and exponent
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (unsigned, None),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let separated = |value: &str| value.chars().all(|c| c == '_' || c.is_ascii_digit());
+
+    separated(whole)
+        && separated(fraction)
+        && digits(mantissa.replace('.', "").as_str(), 10)
+        && exponent.is_none_or(|exponent| digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent), 10))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_reading_as_numbers_are_not_plain() {
+        for name in ["0x10", "0o17", "0b101", "017", "1e3", "-1.5E+3", ".5", "1_000", "+12", "1:30", "190:20:30.15", "-.INF", ".NaN"] {
+            assert!(!is_plain_safe(name), "{} should be quoted", name);
+        }
+    }
+
+    #[test]
+    fn names_close_to_numbers_stay_plain() {
+        for name in ["0x", "0xg1", "1.2.3", "v1.0", "1e", "file1", "2024-01-01.log", "12:75", "_", "1:2:3:4x"] {
+            assert!(is_plain_safe(name), "{} should stay plain", name);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! File System Utility Tools
+//!
+//! Builds filtered directory structures and renders them as trees, SVG diagrams,
+//! JSON or metrics. The `fs-tools` binary is a thin command-line wrapper over this API.
+//!
+//! # Example
+//! ```no_run
+//! use std::path::Path;
+//! use folder_clip_cli::{get_folder_structure, print_tree, FolderStructureOptionsBuilder};
+//!
+//! let options = FolderStructureOptionsBuilder::default()
+//!     .include_extension_only(vec!["rs".to_string()])
+//!     .build()
+//!     .unwrap();
+//! let root = get_folder_structure(Path::new("."), &options)?;
+//! print_tree(&root, &options);
+//!
+//! // Walk every node with its depth, e.g. to find the deepest file
+//! let deepest = root.iter()
+//!     .filter(|(item, _)| matches!(item, folder_clip_cli::Item::File(..)))
+//!     .map(|(_, depth)| depth)
+//!     .max();
+//! println!("deepest file at depth {:?}", deepest);
+//! # Ok::<(), folder_clip_cli::FsError>(())
+//! ```
+
+pub mod folder_utility;
+
+pub use folder_utility::folder_strucure::{
+    get_folder_structure, get_folder_structure_from_source, print_tree, walk, DirSource, EntryMetadata, FolderStructureOptions,
+    FolderStructureOptionsBuilder, FsError, Item, ItemIter, MemoryFs, Progress, RealFs, StreamError, StreamSummary, WalkEvent,
+};
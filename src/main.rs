@@ -1,11 +1,37 @@
-mod folder_utility;
-
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use std::str::FromStr;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use regex::Regex;
 
-use folder_utility::folder_strucure::{print_tree, get_folder_structure, FolderStructureOptionsBuilder};
+use folder_clip_cli::folder_utility::bundles::{bundle, preset, BUNDLE_NAMES, NO_BINARIES, PRESET_NAMES};
+use folder_clip_cli::folder_utility::csv::to_csv;
+use folder_clip_cli::folder_utility::diff::{count_sides, diff_items, write_diff};
+use folder_clip_cli::folder_utility::dot::to_dot;
+use folder_clip_cli::folder_utility::dupes::find_duplicates;
+use folder_clip_cli::folder_utility::gitignore::glob_to_regex;
+use folder_clip_cli::folder_utility::folder_strucure::{find_marker_ancestor, write_breadth_first, write_flat, write_streamed, write_tree, get_folder_structure, Alignment, SortKey, FolderStructureOptions, FolderStructureOptionsBuilder, Charset, ExclusionReason, FilterMode, FsError, Grouping, Item, Progress, StreamError, TimeStyle};
+use folder_clip_cli::folder_utility::json::{to_json, to_json_array, write_ndjson};
+use folder_clip_cli::folder_utility::markdown::to_markdown;
+use folder_clip_cli::folder_utility::prometheus::to_prometheus;
+use folder_clip_cli::folder_utility::search::file_paths;
+use folder_clip_cli::folder_utility::summary::{count_by_extension, Summary};
+use folder_clip_cli::folder_utility::svg::to_svg;
+use folder_clip_cli::folder_utility::terminal::terminal_width;
+use folder_clip_cli::folder_utility::units::{format_size, parse_duration, parse_size};
+use folder_clip_cli::folder_utility::users::resolve_uid;
+use folder_clip_cli::folder_utility::yaml::to_yaml;
+
+/// Name of the file holding exclude patterns at the root of a tree
+const IGNORE_FILE_NAME: &str = ".fstreeignore";
+
+/// Name of the file defining extension presets at the root of a tree
+const PRESETS_FILE_NAME: &str = ".fstreepresets";
 
 #[derive(Parser)]
 #[command(name = "fs-tools")]
@@ -15,88 +41,1518 @@ struct Cli {
     command: Commands,
 }
 
+/// When to color the tree output
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is not set
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// How the tree format lays out the entries
+#[derive(Clone, Copy)]
+enum TreeLayout {
+    /// Indented tree with connectors
+    Nested,
+    /// Relative paths, level by level
+    BreadthFirst,
+    /// Relative file paths in tree order, without indentation
+    Flat,
+}
+
+/// Output formats for the tree command
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Indented tree with box-drawing connectors
+    Tree,
+    /// Self-contained SVG diagram
+    Svg,
+    /// Summary statistics as Prometheus text-format metrics
+    Prometheus,
+    /// Graphviz DOT digraph
+    Dot,
+    /// Nested Markdown bullet list
+    Markdown,
+    /// Nested JSON objects mirroring the tree
+    Json,
+    /// Flat JSON array of entries with their depth, in pre-order
+    JsonArray,
+    /// Nested YAML mappings of folders to their children
+    Yaml,
+    /// One CSV row per file with its relative path, name, extension and size
+    Csv,
+    /// One JSON object per line and per entry, parents first, written while traversing
+    Ndjson,
+}
+
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // Parsed once per run, boxing would only add noise
 enum Commands {
-    /// Display directory structure as a tree
-    Tree {
+    /// Count files by extension, most frequent first
+    Count {
+        /// Directory path to start from
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// File extensions to include (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        include: Option<Vec<String>>,
+
+        /// File extensions to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Option<Vec<String>>,
+
+        /// Regex patterns to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude_pattern: Option<Vec<String>>,
+    },
+
+    /// Find files with identical contents
+    Dupes {
         /// Directory path to start from
         #[arg(default_value = ".")]
         path: PathBuf,
 
+        /// File extensions to include (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        include: Option<Vec<String>>,
+
+        /// File extensions to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Option<Vec<String>>,
+
+        /// Regex patterns to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude_pattern: Option<Vec<String>>,
+
+        /// Also check hidden (dot-prefixed) files and folders
+        #[arg(long)]
+        hidden: bool,
+    },
+
+    /// Compare two directory trees by relative path, exiting with 1 if they differ
+    Diff {
+        /// Directory shown as removed (-) where it has entries the other lacks
+        left: PathBuf,
+
+        /// Directory shown as added (+) where it has entries the other lacks
+        right: PathBuf,
+
+        /// File extensions to include (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        include: Option<Vec<String>>,
+
+        /// File extensions to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Option<Vec<String>>,
+
+        /// Regex patterns to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude_pattern: Option<Vec<String>>,
+
+        /// Also compare hidden (dot-prefixed) files and folders
+        #[arg(long)]
+        hidden: bool,
+    },
+
+    /// Print the path of the first file passing the filters, exiting with 1 if there is none
+    Find {
+        /// Directory path to search
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// File extensions to include (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        include: Option<Vec<String>>,
+
+        /// File extensions to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Option<Vec<String>>,
+
+        /// Regex patterns files must match (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        include_pattern: Option<Vec<String>>,
+
+        /// Regex patterns to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude_pattern: Option<Vec<String>>,
+
+        /// Match the patterns case-insensitively
+        #[arg(long, short)]
+        ignore_case: bool,
+
+        /// Also search hidden (dot-prefixed) files and folders
+        #[arg(long)]
+        hidden: bool,
+    },
+
+    /// Print the paths of the files whose name matches a regex, one per line
+    Search {
+        /// Directory path to search
+        path: PathBuf,
+
+        /// Regex the file names must match
+        pattern: String,
+
+        /// Match the pattern case-insensitively
+        #[arg(long, short)]
+        ignore_case: bool,
+
+        /// Also search hidden (dot-prefixed) files and folders
+        #[arg(long)]
+        hidden: bool,
+
+        /// Regex patterns to exclude (comma-separated, @FILE reads one per line)
+        #[arg(long, value_delimiter = ',')]
+        exclude_pattern: Option<Vec<String>>,
+    },
+
+    /// Display directory structure as a tree
+    Tree {
+        /// Directory paths to start from, each printed as its own tree
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+
         /// Show empty folders
         #[arg(long, short)]
         show_empty: bool,
 
-        /// File extensions to include (comma-separated)
+        /// File extensions to include (comma-separated, @FILE reads one per line)
         #[arg(long, value_delimiter = ',')]
         include: Option<Vec<String>>,
 
-        /// File extensions to exclude (comma-separated)
+        /// File extensions to exclude (comma-separated, @FILE reads one per line)
         #[arg(long, value_delimiter = ',')]
         exclude: Option<Vec<String>>,
 
-        /// Regex patterns to exclude (comma-separated)
+        /// Regex patterns to exclude (comma-separated, @FILE reads one per line)
         #[arg(long, value_delimiter = ',')]
         exclude_pattern: Option<Vec<String>>,
+
+        /// Exclude names matching any --exclude-pattern, or only those matching all of them
+        #[arg(long, value_name = "MODE", default_value = "any")]
+        filter_mode: FilterMode,
+
+        /// Regex patterns matched against the path relative to the root, e.g. '^src/generated$'
+        /// (comma-separated); an entry matching this or --exclude-pattern is excluded
+        #[arg(long, value_delimiter = ',', value_name = "REGEX")]
+        exclude_path_pattern: Option<Vec<String>>,
+
+        /// Regex patterns files must match to be shown (comma-separated); folders are always kept
+        #[arg(long, value_delimiter = ',')]
+        include_pattern: Option<Vec<String>>,
+
+        /// Globs files must match, relative to the root path (comma-separated, e.g. "src/**/*.rs")
+        #[arg(long, value_delimiter = ',')]
+        include_glob: Option<Vec<String>>,
+
+        /// Globs of entries to skip, relative to the root path (comma-separated, e.g. "**/test_*")
+        #[arg(long, value_delimiter = ',')]
+        exclude_glob: Option<Vec<String>>,
+
+        /// Regex patterns of folder names always shown with their subtree, even without matching files (comma-separated)
+        #[arg(long, value_delimiter = ',', value_name = "REGEX")]
+        keep_dir: Option<Vec<String>>,
+
+        /// Match patterns, globs and --mod-files names case-insensitively
+        #[arg(long, short = 'i')]
+        ignore_case: bool,
+
+        /// Only show directories containing a file modified within this window (e.g. 7d, 12h)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        active_dirs: Option<Duration>,
+
+        /// Names to list first in their directory, in the given order (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        pin: Option<Vec<String>>,
+
+        /// Only show files of at least this size (e.g. 500K, 10M)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        min_size: Option<u64>,
+
+        /// Only show files of at most this size (e.g. 500K, 10M)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+
+        /// Only show files whose name has at least N characters
+        #[arg(long, value_name = "N")]
+        min_name_length: Option<usize>,
+
+        /// Only show files whose name has at most N characters, e.g. with --flat to audit long names
+        #[arg(long, value_name = "N")]
+        max_name_length: Option<usize>,
+
+        /// Only show files modified within this window (e.g. 7d, 2h, 30m)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        newer_than: Option<Duration>,
+
+        /// Only show files last modified longer ago than this (e.g. 30d)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        older_than: Option<Duration>,
+
+        /// Show each entry's share of its parent directory's total size
+        #[arg(long)]
+        size_percent: bool,
+
+        /// Show each file's size in human-readable units (e.g. 1.2 KiB), `?` if unreadable
+        #[arg(long)]
+        size: bool,
+
+        /// Also show each folder's total size
+        #[arg(long, requires = "size")]
+        dir_size: bool,
+
+        /// Start each line with the entry's size, folders totalling the files beneath them
+        #[arg(long)]
+        du: bool,
+
+        /// Start each line with the entry's permissions, e.g. rwxr-xr-x (Unix only, ? elsewhere)
+        #[arg(long)]
+        perms: bool,
+
+        /// Start each line with the entry's last modification time, folders showing their most recent file
+        #[arg(long)]
+        mtime: bool,
+
+        /// How --mtime prints times: iso (2024-01-05 14:32, UTC) or relative (3 days ago)
+        #[arg(long, value_name = "STYLE", default_value = "iso", requires = "mtime")]
+        time_style: TimeStyle,
+
+        /// Skip entries ignored by the .gitignore files found in the tree, including nested ones
+        #[arg(long)]
+        gitignore: bool,
+
+        /// Do not read exclude patterns from the root's .fstreeignore file (one glob per line, or a regex after `re:`)
+        #[arg(long)]
+        no_ignore_file: bool,
+
+        /// Show dotfiles and dot-directories
+        #[arg(long)]
+        hidden: bool,
+
+        /// Draw the tree with ASCII characters instead of box-drawing ones
+        #[arg(long)]
+        ascii: bool,
+
+        /// Width of each tree level in characters, connectors included
+        #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u16).range(2..))]
+        indent: u16,
+
+        /// Draw connectors exactly like GNU tree's --charset: utf8 (box drawing, no-break space padding) or ascii
+        #[arg(long, value_name = "CHARSET", conflicts_with_all = ["ascii", "indent"])]
+        charset: Option<Charset>,
+
+        /// Color folders, executables and symlinks in the tree output
+        #[arg(long, value_name = "WHEN", default_value = "auto")]
+        color: ColorChoice,
+
+        /// Shorten names so lines fit in N columns (default: the terminal width when printing to one,
+        /// 80 if it cannot be detected)
+        #[arg(long, value_name = "N")]
+        width: Option<usize>,
+
+        /// Never shorten names to fit the terminal width
+        #[arg(long, conflicts_with = "width")]
+        no_truncate: bool,
+
+        /// Do not end the tree with the number of directories and files
+        #[arg(long)]
+        no_summary: bool,
+
+        /// Indent the json and json-array formats with 2 spaces (compact by default)
+        #[arg(long)]
+        json_pretty: bool,
+
+        /// Exit with 0 even when no file is left after filtering (errors still exit with 2)
+        #[arg(long)]
+        allow_empty: bool,
+
+        /// Print the canonical absolute path of the starting directory as the root line
+        #[arg(long)]
+        print_root_path: bool,
+
+        /// Label printed for the root instead of its name, e.g. project/
+        #[arg(long, value_name = "NAME", conflicts_with = "print_root_path")]
+        root_name: Option<String>,
+
+        /// Traverse symlinked directories; links back to a visited directory are shown as `(cycle)`
+        #[arg(long)]
+        follow_symlinks: bool,
+
+        /// Traverse the root's subdirectories on up to N threads
+        #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+
+        /// Write a running count of scanned entries and the scan rate to stderr while traversing
+        #[arg(long)]
+        progress: bool,
+
+        /// Count the entries of each path before traversing it, so --progress can show a
+        /// percentage and an ETA (estimates: filtered-out folders are counted but not scanned)
+        #[arg(long, requires = "progress")]
+        pre_count: bool,
+
+        /// Abort on the first unreadable directory instead of listing it as [permission denied]
+        #[arg(long)]
+        strict: bool,
+
+        /// List only directories; folders without matching files stay hidden unless --show-empty
+        #[arg(long)]
+        dirs_only: bool,
+
+        /// Sort entries within each directory by name, size (largest first) or mtime (newest first)
+        #[arg(long, value_name = "KEY", default_value = "name")]
+        sort: SortKey,
+
+        /// Reverse the sort order
+        #[arg(long)]
+        reverse: bool,
+
+        /// Order names ignoring case, so apple comes before Zebra
+        #[arg(long)]
+        sort_case_insensitive: bool,
+
+        /// Group directories before files (dirs-first), after them (dirs-last) or not at all (mixed)
+        #[arg(long, value_name = "MODE", default_value = "dirs-first")]
+        group: Grouping,
+
+        /// List files before directories, same as --group dirs-last
+        #[arg(long, alias = "files-first", conflicts_with = "group")]
+        dirs_last: bool,
+
+        /// Write the output to this file instead of stdout
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Do not recurse into reparse points such as junctions (Windows only, no-op elsewhere)
+        #[arg(long)]
+        skip_reparse: bool,
+
+        /// Show each file's inode number and hard link count, flagging hard links (Unix only, no-op elsewhere)
+        #[arg(long)]
+        inodes: bool,
+
+        /// Collapse consecutive empty sibling folders into a single line
+        #[arg(long)]
+        group_empty_runs: bool,
+
+        /// Print at most N children per directory, after sorting, then "... and M more"
+        #[arg(long, value_name = "N")]
+        max_entries: Option<usize>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "tree")]
+        format: OutputFormat,
+
+        /// Only show files owned by this user name or uid (Unix only)
+        #[arg(long, value_name = "USER")]
+        owner: Option<String>,
+
+        /// Hide files owned by this user name or uid (Unix only)
+        #[arg(long, value_name = "USER")]
+        not_owner: Option<String>,
+
+        /// Retry a failed directory read up to N times, e.g. on network mounts. Only transient
+        /// errors (interrupted or timed out reads, I/O errors, stale handles) are retried;
+        /// denied permissions and missing directories fail at once
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        retry: u32,
+
+        /// Flag files whose content (magic bytes) disagrees with their extension
+        #[arg(long)]
+        ext_mismatch: bool,
+
+        /// Only show files whose content (magic bytes) disagrees with their extension
+        #[arg(long)]
+        ext_mismatch_only: bool,
+
+        /// Traverse and print entries level by level, each with its path relative to the root
+        #[arg(long)]
+        breadth_first: bool,
+
+        /// Traverse and print each folder's contents before its siblings (default)
+        #[arg(long, conflicts_with = "breadth_first")]
+        depth_first: bool,
+
+        /// Print one relative file path per line, without tree drawing (folders with --dirs-only)
+        #[arg(long, conflicts_with = "breadth_first")]
+        flat: bool,
+
+        /// Print each entry as soon as it is found instead of building the whole tree first.
+        /// Empty folders are listed, and options needing the whole tree such as --du are ignored
+        #[arg(long, conflicts_with_all = ["breadth_first", "flat", "format"])]
+        stream: bool,
+
+        /// Stop traversing once the collected files reach this size (e.g. 500M).
+        /// Which files are collected depends on the file system's listing order
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        stop_at_size: Option<u64>,
+
+        /// Stop traversing after visiting N entries in total, printing what was collected with a warning
+        #[arg(long, value_name = "N")]
+        max_total_entries: Option<usize>,
+
+        /// Use the nearest ancestor containing this entry (e.g. .git) as the root,
+        /// highlighting the given path inside it
+        #[arg(long, value_name = "MARKER")]
+        up_to: Option<String>,
+
+        /// Exclude common binary, image and archive extensions (ignored with --include)
+        #[arg(long)]
+        no_binaries: bool,
+
+        /// Include the extensions of a named preset: code, config, docs, images, or one defined
+        /// in the root's .fstreepresets (repeatable, added to --include)
+        #[arg(long, value_name = "NAME")]
+        preset: Vec<String>,
+
+        /// Print the extensions of a named bundle (e.g. no-binaries) and exit
+        #[arg(long, value_name = "NAME")]
+        show_bundle: Option<String>,
+
+        /// Only show the ancestors of entries whose path ends with this path (e.g. src/main.rs)
+        #[arg(long, value_name = "PATH")]
+        path_to: Option<String>,
+
+        /// With --path-to, also list the siblings of the matched entries
+        #[arg(long, requires = "path_to")]
+        path_to_siblings: bool,
+
+        /// Only show directories with at least N immediate children, and their ancestors
+        #[arg(long, value_name = "N")]
+        min_children: Option<usize>,
+
+        /// Annotate directories with the number of distinct extensions among their files
+        #[arg(long)]
+        type_diversity: bool,
+
+        /// Flag directories whose files have more than N distinct extensions (implies --type-diversity)
+        #[arg(long, value_name = "N")]
+        max_types: Option<usize>,
+
+        /// List but do not enter directories containing a file with one of these names (comma-separated)
+        #[arg(long, value_delimiter = ',', value_name = "FILENAME")]
+        skip_dir_with: Option<Vec<String>>,
+
+        /// List directories with one of these names as a single line with their entry count, e.g. node_modules (repeatable)
+        #[arg(long, value_name = "NAME")]
+        collapse: Vec<String>,
+
+        /// Line up annotations: per directory (dir, the default), across the tree (global),
+        /// or at a fixed column width (e.g. --align=global)
+        #[arg(long, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "dir")]
+        align: Option<Alignment>,
+
+        /// Prefix each line with its zero-padded depth and relative path, for grep/awk
+        #[arg(long)]
+        depth_column: bool,
+
+        /// Width of the relative path column of --depth-column (defaults to the widest path)
+        #[arg(long, value_name = "N", requires = "depth_column")]
+        depth_column_width: Option<usize>,
+
+        /// Prefix each line with its depth in brackets, the root being depth 0
+        #[arg(long)]
+        show_depth: bool,
+
+        /// Print each entry as its path relative to the root instead of its name
+        #[arg(long)]
+        full_path: bool,
+
+        /// Only show files whose stem equals their parent directory name (e.g. foo/foo.rs)
+        #[arg(long)]
+        mod_files: bool,
+
+        /// Compare names case-insensitively for --mod-files
+        #[arg(long, requires = "mod_files")]
+        mod_files_ignore_case: bool,
+
+        /// Annotate each entry with the reason it passed the filters (slower, verbose)
+        #[arg(long)]
+        explain: bool,
+
+        /// Write each entry left out of the tree to stderr, with the reason it was skipped
+        #[arg(long)]
+        report_skips: bool,
+
+        /// Stop descending after N levels; 0 shows only the root's immediate children
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+
+        /// Hide entries shallower than N levels, starting the tree at their descendants
+        #[arg(long, value_name = "N")]
+        min_depth: Option<usize>,
     },
 }
 
 fn main() {
-    let cli_args = Cli::parse();
+    let mut cli_args = Cli::parse();
+    if let Err(e) = expand_filter_files(&mut cli_args.command) {
+        eprintln!("Error: {}", e);
+        process::exit(2);
+    }
+
     match cli_args.command {
         Commands::Tree { 
-            path, 
+            paths,
             show_empty, 
             include, 
             exclude, 
-            exclude_pattern 
+            exclude_pattern,
+            filter_mode,
+            exclude_path_pattern,
+            include_pattern,
+            include_glob,
+            exclude_glob,
+            keep_dir,
+            ignore_case,
+            active_dirs,
+            pin,
+            size_percent,
+            min_size,
+            max_size,
+            min_name_length,
+            max_name_length,
+            newer_than,
+            older_than,
+            size,
+            dir_size,
+            du,
+            perms,
+            mtime,
+            time_style,
+            gitignore,
+            no_ignore_file,
+            hidden,
+            color,
+            width,
+            no_truncate,
+            ascii,
+            indent,
+            charset,
+            no_summary,
+            json_pretty,
+            allow_empty,
+            print_root_path,
+            root_name,
+            follow_symlinks,
+            output,
+            jobs,
+            progress,
+            pre_count,
+            dirs_only,
+            strict,
+            sort,
+            reverse,
+            sort_case_insensitive,
+            group,
+            dirs_last,
+            skip_reparse,
+            inodes,
+            group_empty_runs,
+            max_entries,
+            format,
+            owner,
+            not_owner,
+            retry,
+            ext_mismatch,
+            ext_mismatch_only,
+            breadth_first,
+            stream,
+            flat,
+            depth_first: _,
+            stop_at_size,
+            max_total_entries,
+            up_to,
+            no_binaries,
+            preset,
+            show_bundle,
+            path_to,
+            path_to_siblings,
+            min_children,
+            type_diversity,
+            max_types,
+            skip_dir_with,
+            collapse,
+            align,
+            depth_column,
+            depth_column_width,
+            show_depth,
+            full_path,
+            mod_files,
+            mod_files_ignore_case,
+            explain,
+            report_skips,
+            max_depth,
+            min_depth,
         } => {
+            if let Some(name) = show_bundle {
+                match bundle(&name) {
+                    Some(extensions) => println!("{}", extensions.join(",")),
+                    None => {
+                        eprintln!("Unknown bundle '{}' (available: {})", name, BUNDLE_NAMES.join(", "));
+                        process::exit(2);
+                    }
+                }
+                return;
+            }
+
             let mut options_builder = FolderStructureOptionsBuilder::default();
             options_builder.show_empty_folder(show_empty);
+            options_builder.active_within(active_dirs);
+            options_builder.pinned_names(pin.unwrap_or_default());
+            options_builder.show_size_percent(size_percent);
+            options_builder.min_size(min_size);
+            options_builder.max_size(max_size);
+            options_builder.min_name_length(min_name_length);
+            options_builder.max_name_length(max_name_length);
+            options_builder.newer_than(newer_than);
+            options_builder.older_than(older_than);
+            options_builder.show_size(size);
+            options_builder.show_folder_size(dir_size);
+            options_builder.du(du);
+            options_builder.show_perms(perms);
+            options_builder.mtime_style(mtime.then_some(time_style));
+            options_builder.respect_gitignore(gitignore);
+            options_builder.show_hidden(hidden);
+            options_builder.show_summary(!no_summary);
+            options_builder.json_pretty(json_pretty);
+            options_builder.print_root_path(print_root_path);
+            options_builder.root_name(root_name);
+            options_builder.follow_symlinks(follow_symlinks);
+            options_builder.jobs(usize::from(jobs));
+            // A progress line would end up among the output when stderr is redirected
+            let progress_line = (progress && io::stderr().is_terminal()).then(|| Arc::new(ProgressLine::new()));
+            if let Some(line) = &progress_line {
+                let line = Arc::clone(line);
+                options_builder.on_progress(Some(Arc::new(move |progress| line.render(progress))));
+            }
+            options_builder.dirs_only(dirs_only);
+            options_builder.strict(strict);
+            options_builder.sort_key(sort);
+            options_builder.reverse_sort(reverse);
+            options_builder.sort_case_insensitive(sort_case_insensitive);
+            options_builder.grouping(if dirs_last { Grouping::DirsLast } else { group });
+            options_builder.ascii(ascii);
+            options_builder.indent(usize::from(indent));
+            options_builder.charset(charset);
+            options_builder.color(match color {
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+                ColorChoice::Auto => {
+                    output.is_none()
+                        && io::stdout().is_terminal()
+                        && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                }
+            });
+            options_builder.line_width(match width {
+                Some(width) => Some(width),
+                None if no_truncate || output.is_some() || !io::stdout().is_terminal() => None,
+                None => Some(terminal_width()),
+            });
+            options_builder.skip_reparse(skip_reparse);
+            options_builder.show_inodes(inodes);
+            options_builder.group_empty_runs(group_empty_runs);
+            options_builder.max_entries(max_entries);
+            options_builder.read_retries(retry);
+            options_builder.check_ext_mismatch(ext_mismatch);
+            options_builder.ext_mismatch_only(ext_mismatch_only);
+            options_builder.stop_at_size(stop_at_size);
+            options_builder.max_total_entries(max_total_entries);
+            options_builder.path_to(path_to.clone());
+            options_builder.path_to_siblings(path_to_siblings);
+            options_builder.min_children(min_children);
+            options_builder.show_type_diversity(type_diversity);
+            options_builder.max_types(max_types);
+            options_builder.skip_dir_with(skip_dir_with.unwrap_or_default());
+            options_builder.collapse_dirs(collapse);
+            options_builder.alignment(align);
+            options_builder.depth_column(depth_column);
+            options_builder.depth_column_width(depth_column_width);
+            options_builder.show_depth(show_depth);
+            options_builder.full_path(full_path);
+            options_builder.mod_files_only(mod_files);
+            options_builder.mod_files_ignore_case(mod_files_ignore_case);
+            options_builder.explain(explain);
+            if report_skips {
+                options_builder.on_skip(Some(Arc::new(|path: &str, reason: &ExclusionReason| eprintln!("skipped {}: {}", path, reason))));
+            }
+            options_builder.max_depth(max_depth);
+            options_builder.min_depth(min_depth.unwrap_or(0));
+            options_builder.breadth_first(breadth_first);
+
+            // An include list already restricts the tree to chosen extensions,
+            // so the bundle only applies when no include list is given
+            let apply_no_binaries = no_binaries && include.is_none() && preset.is_empty();
+
+            let include_ext = include.map(|include_ext| include_ext.iter()
+                .map(|s| s.trim_start_matches('.').to_string())
+                .collect::<Vec<_>>());
+            if let Some(include_ext) = &include_ext {
+                options_builder.include_extension_only(include_ext.clone());
+            }
+
+            let mut exclude_ext = exclude.unwrap_or_default()
+                .iter()
+                .map(|s| s.trim_start_matches('.').to_string())
+                .collect::<Vec<_>>();
+
+            if apply_no_binaries {
+                exclude_ext.extend(NO_BINARIES.iter().map(|ext| ext.to_string()));
+            }
 
-            if let Some(include_ext) = include {
-                options_builder.include_extension_only(
-                    include_ext.iter()
-                        .map(|s| s.trim_start_matches('.').to_string())
-                        .collect()
-                );
+            if !exclude_ext.is_empty() {
+                options_builder.exclude_extension(exclude_ext);
             }
 
-            if let Some(exclude_ext) = exclude {
-                options_builder.exclude_extension(
-                    exclude_ext.iter()
-                        .map(|s| s.trim_start_matches('.').to_string())
-                        .collect()
-                );
+            let exclude_regexes = compile_patterns(&exclude_pattern.unwrap_or_default());
+            options_builder.filter_mode(filter_mode);
+            options_builder.exclude_by_path(compile_patterns(&exclude_path_pattern.unwrap_or_default()));
+
+            if let Some(patterns) = include_pattern {
+                options_builder.include_by_filter(compile_patterns(&patterns));
+            }
+
+            options_builder.include_globs(include_glob.unwrap_or_default());
+            options_builder.ignore_case(ignore_case);
+            options_builder.exclude_globs(exclude_glob.unwrap_or_default());
+            options_builder.keep_dirs(compile_patterns(&keep_dir.unwrap_or_default()));
+
+            if owner.is_some() || not_owner.is_some() {
+                if cfg!(unix) {
+                    for (user, is_owner) in [(owner, true), (not_owner, false)] {
+                        let Some(user) = user else { continue };
+                        match resolve_uid(&user) {
+                            Ok(uid) if is_owner => { options_builder.owner_uid(Some(uid)); }
+                            Ok(uid) => { options_builder.not_owner_uid(Some(uid)); }
+                            Err(e) => {
+                                eprintln!("Error: {}", e);
+                                process::exit(2);
+                            }
+                        }
+                    }
+                } else {
+                    eprintln!("Warning: --owner and --not-owner are only supported on Unix and are ignored");
+                }
             }
 
-            if let Some(patterns) = exclude_pattern {
-                let regexes = patterns.iter()
-                    .filter_map(|p| match Regex::new(p) {
-                        Ok(re) => Some(re),
+            let mut out: Box<dyn Write> = match &output {
+                Some(file) => match File::create(file) {
+                    Ok(created) => Box::new(BufWriter::new(created)),
+                    Err(e) => {
+                        eprintln!("Error writing '{}': {}", file.display(), e);
+                        process::exit(2);
+                    }
+                },
+                // Stdout is line-buffered, batching lines avoids a flush per entry
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
+
+            let layout = if flat {
+                TreeLayout::Flat
+            } else if breadth_first {
+                TreeLayout::BreadthFirst
+            } else {
+                TreeLayout::Nested
+            };
+
+            // Each path is rendered on its own, so a failing one does not prevent the others
+            let mut written = 0;
+            let mut found_files = false;
+            let mut had_error = false;
+            for path in &paths {
+                let mut path = path.clone();
+                let mut path_builder = options_builder.clone();
+                if let Some(marker) = &up_to {
+                    match find_marker_ancestor(&path, marker) {
+                        Some(ancestor) => {
+                            path_builder.highlight_path(path.canonicalize().ok());
+                            path = ancestor;
+                        }
+                        None => {
+                            eprintln!("Error: no ancestor of '{}' contains '{}'", path.display(), marker);
+                            continue;
+                        }
+                    }
+                }
+
+                // Presets may be defined at the root, so each path expands them on its own
+                if !preset.is_empty() {
+                    let mut extensions = include_ext.clone().unwrap_or_default();
+                    match expand_presets(&preset, &path) {
+                        Ok(preset_ext) => extensions.extend(preset_ext),
                         Err(e) => {
-                            eprintln!("Invalid regex pattern '{}': {}", p, e);
-                            None
+                            eprintln!("Error: {}", e);
+                            process::exit(2);
+                        }
+                    }
+                    path_builder.include_extension_only(extensions);
+                }
+
+                if let Some(line) = progress_line.as_ref().filter(|_| pre_count) {
+                    line.count_ahead(&path, hidden);
+                }
+
+                path_builder.exclude_by_filter(exclude_regexes.clone());
+                if !no_ignore_file {
+                    path_builder.ignore_patterns(compile_patterns(&read_ignore_file(&path)));
+                }
+
+                let options = match path_builder.build() {
+                    Ok(opt) => opt,
+                    Err(e) => {
+                        eprintln!("Error building options: {}", e);
+                        process::exit(2);
+                    }
+                };
+
+                // Streamed output is written while traversing, without building the structure
+                if stream || matches!(format, OutputFormat::Ndjson) {
+                    let streamed = if matches!(format, OutputFormat::Ndjson) {
+                        write_ndjson(&path, &options, &mut out)
+                    } else {
+                        let separated = if written > 0 { writeln!(out) } else { Ok(()) };
+                        separated.map_err(StreamError::Write).and_then(|_| write_streamed(&path, &options, &mut out))
+                    };
+                    let flushed = match streamed {
+                        Ok(summary) => {
+                            found_files |= summary.files > 0;
+                            if summary.entry_limit_reached {
+                                warn_entry_limit(max_total_entries);
+                            }
+                            out.flush()
                         }
-                    })
-                    .collect::<Vec<_>>();
-                
-                options_builder.exclude_by_filter(regexes);
+                        Err(StreamError::Traversal(e)) => {
+                            eprintln!("Error: {}", e);
+                            had_error = true;
+                            out.flush()
+                        }
+                        Err(StreamError::Write(e)) => Err(e),
+                    };
+                    if let Err(e) = flushed {
+                        // Stdout errors such as a closed pipe simply end the output early
+                        if let Some(file) = &output {
+                            eprintln!("Error writing '{}': {}", file.display(), e);
+                            process::exit(2);
+                        }
+                        return;
+                    }
+                    written += 1;
+                    continue;
+                }
+
+                let root = match get_folder_structure(&path, &options) {
+                    Ok(root) => {
+                        if root.metadata().entry_limit_reached {
+                            warn_entry_limit(max_total_entries);
+                        }
+                        root
+                    }
+                    Err(FsError::NoMatch) => {
+                        eprintln!("Error: no entry of '{}' matches '{}'", path.display(), path_to.as_deref().unwrap_or_default());
+                        had_error = true;
+                        continue;
+                    }
+                    Err(FsError::EmptyFolder) => {
+                        // Nothing to show is a result, not a failure
+                        eprintln!("{}", describe_empty_root(&path));
+                        continue;
+                    }
+                    Err(e) => {
+                        // With several paths, errors that do not name their own path are told apart
+                        if paths.len() > 1 && !matches!(e, FsError::IoError(_, Some(_))) {
+                            eprintln!("Error in '{}': {}", path.display(), e);
+                        } else {
+                            eprintln!("Error: {}", e);
+                        }
+                        had_error = true;
+                        continue;
+                    }
+                };
+
+                // Flushing after each root keeps the output ordered with the errors of the next ones
+                let result = if written > 0 { writeln!(out) } else { Ok(()) }
+                    .and_then(|_| write_output(&mut out, &root, &options, format, layout, &path))
+                    .and_then(|_| out.flush());
+                if let Err(e) = result {
+                    // Stdout errors such as a closed pipe simply end the output early
+                    if let Some(file) = &output {
+                        eprintln!("Error writing '{}': {}", file.display(), e);
+                        process::exit(2);
+                    }
+                    return;
+                }
+                written += 1;
+                found_files |= root.has_terminal_file();
             }
 
+            if let Err(e) = out.flush() {
+                if let Some(file) = &output {
+                    eprintln!("Error writing '{}': {}", file.display(), e);
+                    had_error = true;
+                }
+            }
+
+            // Scripts can tell a failure and an empty result from a successful listing,
+            // --allow-empty only waiving the latter
+            if had_error {
+                process::exit(2);
+            }
+            if !found_files && !allow_empty {
+                process::exit(1);
+            }
+        },
+        Commands::Count {
+            path,
+            include,
+            exclude,
+            exclude_pattern,
+        } => {
+            let mut options_builder = FolderStructureOptionsBuilder::default();
+            options_builder.include_extension_only(include.unwrap_or_default());
+            options_builder.exclude_extension(exclude.unwrap_or_default());
+            options_builder.exclude_by_filter(compile_patterns(&exclude_pattern.unwrap_or_default()));
+
             let options = match options_builder.build() {
                 Ok(opt) => opt,
                 Err(e) => {
                     eprintln!("Error building options: {}", e);
+                    process::exit(2);
+                }
+            };
+
+            match get_folder_structure(&path, &options) {
+                Ok(root) => {
+                    let counts = count_by_extension(&root, &options);
+                    let width = counts.iter()
+                        .map(|(ext, _)| ext.chars().count())
+                        .chain(["EXTENSION".len()])
+                        .max()
+                        .unwrap_or(0);
+                    println!("{:<width$}  FILES", "EXTENSION");
+                    for (ext, count) in counts {
+                        println!("{:<width$}  {:>5}", ext, count);
+                    }
+                }
+                Err(FsError::EmptyFolder) => println!("No matching files"),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        },
+        Commands::Search {
+            path,
+            pattern,
+            ignore_case,
+            hidden,
+            exclude_pattern,
+        } => {
+            let pattern = match Regex::new(&pattern) {
+                Ok(re) => re,
+                Err(e) => {
+                    eprintln!("Invalid regex pattern '{}': {}", pattern, e);
                     return;
                 }
             };
 
+            let mut options_builder = FolderStructureOptionsBuilder::default();
+            options_builder.include_by_filter(vec![pattern]);
+            options_builder.ignore_case(ignore_case);
+            options_builder.exclude_by_filter(compile_patterns(&exclude_pattern.unwrap_or_default()));
+            options_builder.show_hidden(hidden);
+
+            let options = match options_builder.build() {
+                Ok(opt) => opt,
+                Err(e) => {
+                    eprintln!("Error building options: {}", e);
+                    process::exit(2);
+                }
+            };
+
             match get_folder_structure(&path, &options) {
-                Ok(root) => print_tree(&root, &options),
-                Err(e) => eprintln!("Error creating folder tree: {:?}", e),
+                Ok(root) => {
+                    let mut out = BufWriter::new(io::stdout().lock());
+                    for relative in file_paths(&root, &options) {
+                        if writeln!(out, "{}", display_path(&path, &relative)).is_err() {
+                            break;
+                        }
+                    }
+                    let _ = out.flush();
+                }
+                Err(FsError::EmptyFolder) => {}
+                Err(e) => eprintln!("Error: {}", e),
             }
         },
-        _ => eprint!("Unknown cmd")
+        Commands::Find {
+            path,
+            include,
+            exclude,
+            include_pattern,
+            exclude_pattern,
+            ignore_case,
+            hidden,
+        } => {
+            let mut options_builder = FolderStructureOptionsBuilder::default();
+            options_builder.include_extension_only(include.unwrap_or_default());
+            options_builder.exclude_extension(exclude.unwrap_or_default());
+            options_builder.include_by_filter(compile_patterns(&include_pattern.unwrap_or_default()));
+            options_builder.exclude_by_filter(compile_patterns(&exclude_pattern.unwrap_or_default()));
+            options_builder.ignore_case(ignore_case);
+            options_builder.show_hidden(hidden);
+            options_builder.first_only(true);
+
+            let options = match options_builder.build() {
+                Ok(opt) => opt,
+                Err(e) => {
+                    eprintln!("Error building options: {}", e);
+                    process::exit(2);
+                }
+            };
+
+            match get_folder_structure(&path, &options) {
+                Ok(root) => match file_paths(&root, &options).first() {
+                    Some(relative) => println!("{}", display_path(&path, relative)),
+                    None => process::exit(1),
+                },
+                Err(FsError::EmptyFolder) => process::exit(1),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(2);
+                }
+            }
+        },
+        Commands::Dupes {
+            path,
+            include,
+            exclude,
+            exclude_pattern,
+            hidden,
+        } => {
+            let mut options_builder = FolderStructureOptionsBuilder::default();
+            options_builder.include_extension_only(include.unwrap_or_default());
+            options_builder.exclude_extension(exclude.unwrap_or_default());
+            options_builder.exclude_by_filter(compile_patterns(&exclude_pattern.unwrap_or_default()));
+            options_builder.show_hidden(hidden);
+
+            let options = match options_builder.build() {
+                Ok(opt) => opt,
+                Err(e) => {
+                    eprintln!("Error building options: {}", e);
+                    process::exit(2);
+                }
+            };
+
+            match get_folder_structure(&path, &options) {
+                Ok(root) => {
+                    let duplicates = find_duplicates(&path, &root, &options);
+                    for error in &duplicates.unreadable {
+                        eprintln!("Warning: skipped {}", error);
+                    }
+                    if duplicates.groups.is_empty() {
+                        println!("No duplicate files");
+                    }
+                    for (i, group) in duplicates.groups.iter().enumerate() {
+                        if i > 0 {
+                            println!();
+                        }
+                        println!("{} files, {} each", group.paths.len(), format_size(group.size));
+                        for relative in &group.paths {
+                            println!("  {}", display_path(&path, relative));
+                        }
+                    }
+                }
+                Err(FsError::EmptyFolder) => println!("No duplicate files"),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        },
+        Commands::Diff {
+            left,
+            right,
+            include,
+            exclude,
+            exclude_pattern,
+            hidden,
+        } => {
+            let mut options_builder = FolderStructureOptionsBuilder::default();
+            options_builder.include_extension_only(include.unwrap_or_default());
+            options_builder.exclude_extension(exclude.unwrap_or_default());
+            options_builder.exclude_by_filter(compile_patterns(&exclude_pattern.unwrap_or_default()));
+            options_builder.show_hidden(hidden);
+
+            let options = match options_builder.build() {
+                Ok(opt) => opt,
+                Err(e) => {
+                    eprintln!("Error building options: {}", e);
+                    process::exit(2);
+                }
+            };
+
+            // A root without any matching entry compares as an empty folder
+            let mut trees = Vec::new();
+            for path in [&left, &right] {
+                match get_folder_structure(path, &options) {
+                    Ok(Item::Folder(_, items, ..)) => trees.push(items),
+                    Ok(Item::File(..)) => {
+                        eprintln!("Error: '{}' is not a directory", path.display());
+                        process::exit(2);
+                    }
+                    Err(FsError::EmptyFolder) => trees.push(Vec::new()),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(2);
+                    }
+                }
+            }
+
+            let merged = diff_items(&trees[0], &trees[1], &options);
+            let counts = count_sides(&merged);
+            let mut out = BufWriter::new(io::stdout().lock());
+            let _ = writeln!(out, "--- {}", left.display())
+                .and_then(|_| writeln!(out, "+++ {}", right.display()))
+                .and_then(|_| write_diff(&merged, &mut out))
+                .and_then(|_| writeln!(out, "\n{} only in {}, {} only in {}, {} in both",
+                    counts.left, left.display(), counts.right, right.display(), counts.both))
+                .and_then(|_| out.flush());
+
+            if counts.left > 0 || counts.right > 0 {
+                process::exit(1);
+            }
+        },
+    }
+}
+
+/// Writes the folder structure in the requested format
+///
+/// # Arguments
+/// * `out` - The sink to write to
+/// * `root` - The root item of the structure
+/// * `options` - Configuration options for display
+/// * `format` - The output format
+/// * `layout` - How the tree format lays out the entries
+/// * `root_path` - The traversal root, used to label metrics
+///
+/// # Returns
+/// * `io::Result<()>` - The first error returned by the sink, if any
+fn write_output<W: Write>(out: &mut W, root: &Item, options: &FolderStructureOptions, format: OutputFormat, layout: TreeLayout, root_path: &Path) -> io::Result<()> {
+    match format {
+        OutputFormat::Tree => match layout {
+            TreeLayout::Nested => write_tree(root, options, out),
+            TreeLayout::BreadthFirst => write_breadth_first(root, options, out),
+            TreeLayout::Flat => write_flat(root, options, out),
+        },
+        OutputFormat::Svg => write!(out, "{}", to_svg(root, options)),
+        OutputFormat::Dot => write!(out, "{}", to_dot(root, options)),
+        OutputFormat::Markdown => write!(out, "{}", to_markdown(root, options)),
+        OutputFormat::Json => writeln!(out, "{}", to_json(root, options)),
+        OutputFormat::JsonArray => writeln!(out, "{}", to_json_array(root, options)),
+        OutputFormat::Yaml => write!(out, "{}", to_yaml(root, options)),
+        OutputFormat::Csv => write!(out, "{}", to_csv(root, options)),
+        OutputFormat::Ndjson => unreachable!("ndjson is written while traversing"),
+        OutputFormat::Prometheus => {
+            let summary = Summary::from_item(root, options);
+            write!(out, "{}", to_prometheus(&summary, &root_path.display().to_string()))
+        }
+    }
+}
+
+/// Expands the `@file` arguments of the `--include`, `--exclude` and `--exclude-pattern` lists
+///
+/// # Arguments
+/// * `command` - The parsed command, updated in place
+///
+/// # Returns
+/// * `Result<(), String>` - An error naming the list file that could not be read
+fn expand_filter_files(command: &mut Commands) -> Result<(), String> {
+    let lists = match command {
+        Commands::Tree { include, exclude, exclude_pattern, .. }
+        | Commands::Count { include, exclude, exclude_pattern, .. }
+        | Commands::Find { include, exclude, exclude_pattern, .. }
+        | Commands::Dupes { include, exclude, exclude_pattern, .. }
+        | Commands::Diff { include, exclude, exclude_pattern, .. } => vec![include, exclude, exclude_pattern],
+        Commands::Search { exclude_pattern, .. } => vec![exclude_pattern],
+    };
+
+    for list in lists.into_iter().flatten() {
+        *list = expand_file_args(list)?;
+    }
+    Ok(())
+}
+
+/// Replaces each `@path` value by the lines of the file at `path`
+///
+/// Lines are trimmed and blank ones skipped. Other values are kept as they are.
+///
+/// # Arguments
+/// * `values` - The values given on the command line
+///
+/// # Returns
+/// * `Result<Vec<String>, String>` - The expanded values, or an error naming the unreadable file
+fn expand_file_args(values: &[String]) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::new();
+    for value in values {
+        let Some(file) = value.strip_prefix('@') else {
+            expanded.push(value.clone());
+            continue;
+        };
+        let contents = fs::read_to_string(file).map_err(|e| format!("cannot read '{}': {}", file, e))?;
+        expanded.extend(contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string));
+    }
+    Ok(expanded)
+}
+
+/// Explains why a root yielded no tree, telling empty directories from filtered ones
+///
+/// # Arguments
+/// * `root` - The traversal root
+///
+/// # Returns
+/// * `String` - The message to show
+fn describe_empty_root(root: &Path) -> String {
+    let is_empty = fs::read_dir(root).is_ok_and(|mut entries| entries.next().is_none());
+    if is_empty {
+        format!("'{}' is empty", root.display())
+    } else {
+        format!("No entry of '{}' passes the filters (use --show-empty to list empty folders)", root.display())
+    }
+}
+
+/// Progress line rendered on stderr while the paths of the tree command are traversed
+///
+/// The counts and the rate run over all the paths, which are traversed one after the other.
+struct ProgressLine {
+    /// When the first traversal started
+    start: Instant,
+
+    /// Entries counted ahead by `--pre-count`, if any
+    total: AtomicUsize,
+
+    /// Entries scanned by the traversals that already ended
+    finished: AtomicUsize,
+
+    /// Milliseconds spent counting ahead, left out of the scan rate
+    counting_ms: AtomicU64,
+
+    /// Length of the line currently shown, 0 when nothing is shown
+    width: AtomicUsize,
+}
+
+impl ProgressLine {
+    fn new() -> Self {
+        ProgressLine {
+            start: Instant::now(),
+            total: AtomicUsize::new(0),
+            finished: AtomicUsize::new(0),
+            counting_ms: AtomicU64::new(0),
+            width: AtomicUsize::new(0),
+        }
     }
+
+    /// Adds the entries below a path to the expected total, before the path is traversed
+    ///
+    /// # Arguments
+    /// * `path` - The traversal root
+    /// * `hidden` - Whether dot-prefixed entries are traversed, and so counted
+    fn count_ahead(&self, path: &Path, hidden: bool) {
+        let start = Instant::now();
+        self.show("Counting entries...".to_string());
+        self.total.fetch_add(count_entries(path, hidden), Ordering::Relaxed);
+        self.counting_ms.fetch_add(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Renders the progress of a traversal, erasing the line once the traversal ends
+    fn render(&self, progress: Progress) {
+        match progress {
+            Progress::Scanned(scanned) => {
+                let scanned = self.finished.load(Ordering::Relaxed) + scanned;
+                let total = Some(self.total.load(Ordering::Relaxed)).filter(|&total| total > 0);
+                let counting = Duration::from_millis(self.counting_ms.load(Ordering::Relaxed));
+                self.show(progress_message(scanned, total, self.start.elapsed().saturating_sub(counting)));
+            }
+            Progress::Done(scanned) => {
+                self.finished.fetch_add(scanned, Ordering::Relaxed);
+                let width = self.width.swap(0, Ordering::Relaxed);
+                if width > 0 {
+                    eprint!("\r{:width$}\r", "");
+                }
+            }
+        }
+    }
+
+    /// Replaces the line currently shown, padding over the end of a longer one
+    fn show(&self, message: String) {
+        let width = self.width.swap(message.len(), Ordering::Relaxed);
+        eprint!("\r{:width$}", message);
+    }
+}
+
+/// Formats the progress line written to stderr during traversal
+///
+/// # Arguments
+/// * `scanned` - Number of entries scanned so far
+/// * `total` - Number of entries counted ahead, if they were
+/// * `elapsed` - Time spent scanning so far
+///
+/// # Returns
+/// * `String` - The progress line, without line terminator
+fn progress_message(scanned: usize, total: Option<usize>, elapsed: Duration) -> String {
+    let rate = scanned as f64 / elapsed.as_secs_f64().max(0.001);
+    match total {
+        Some(total) => {
+            // Filtered-out folders are counted but never scanned, so the estimate errs long
+            let percent = (scanned * 100 / total).min(100);
+            let remaining = Duration::from_secs_f64(total.saturating_sub(scanned) as f64 / rate);
+            format!("Scanning... {}/{} entries ({}%), {:.0}/s, ETA {}", scanned, total, percent, rate, format_eta(remaining))
+        }
+        None => format!("Scanning... {} entries, {:.0}/s", scanned, rate),
+    }
+}
+
+/// Formats a remaining time as `45s`, `3m05s` or `1h20m`
+fn format_eta(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+/// Counts the entries below a folder for `--pre-count`, without following symlinks
+///
+/// Unreadable folders count as empty.
+///
+/// # Arguments
+/// * `path` - The folder to count the entries of
+/// * `hidden` - Whether dot-prefixed entries are counted and descended into
+///
+/// # Returns
+/// * `usize` - The number of files and folders below `path`
+fn count_entries(path: &Path, hidden: bool) -> usize {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| hidden || !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| {
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            1 + if is_dir { count_entries(&entry.path(), hidden) } else { 0 }
+        })
+        .sum()
+}
+
+/// Warns that `--max-total-entries` cut a traversal short
+fn warn_entry_limit(max_total_entries: Option<usize>) {
+    eprintln!(
+        "Warning: traversal stopped after {} entries, the structure is incomplete",
+        max_total_entries.unwrap_or_default()
+    );
+}
+
+/// Reads the exclude patterns of the `.fstreeignore` file at the root of a traversal
+///
+/// The file holds one pattern per line, matched against entry names:
+/// - `*.log` - A glob, where `*` and `?` match any characters and `[abc]` a class
+/// - `re:^tmp_\d+$` - A regex after the `re:` prefix, like `--exclude-pattern`
+///
+/// Blank lines and lines starting with `#` are ignored.
+///
+/// # Arguments
+/// * `root` - The traversal root
+///
+/// # Returns
+/// * `Vec<String>` - The patterns as regex sources, empty if the file is missing or unreadable
+fn read_ignore_file(root: &Path) -> Vec<String> {
+    fs::read_to_string(root.join(IGNORE_FILE_NAME))
+        .map(|contents| contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.strip_prefix("re:") {
+                Some(regex) => regex.to_string(),
+                None => glob_to_regex(line),
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Expands preset names into the union of their extensions
+///
+/// Presets defined in the root's `.fstreepresets` take precedence over the
+/// built-in ones of the same name. Each line of the file reads
+/// `name = ext, ext, ...`; blank lines and lines starting with `#` are ignored.
+///
+/// # Arguments
+/// * `names` - The preset names, in the order given
+/// * `root` - The traversal root holding the presets file
+///
+/// # Returns
+/// * `Result<Vec<String>, String>` - The extensions, or an error naming the unknown preset
+fn expand_presets(names: &[String], root: &Path) -> Result<Vec<String>, String> {
+    let defined = fs::read_to_string(root.join(PRESETS_FILE_NAME)).unwrap_or_default();
+    let defined = defined.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, extensions)| (name.trim(), extensions))
+        .collect::<Vec<_>>();
+
+    let mut extensions = Vec::new();
+    for name in names {
+        // The last definition of a name wins, like a later assignment
+        let expansion: Vec<String> = match defined.iter().rev().find(|(defined_name, _)| defined_name == name) {
+            Some((_, list)) => list.split(',')
+                .map(|ext| ext.trim().trim_start_matches('.').to_string())
+                .filter(|ext| !ext.is_empty())
+                .collect(),
+            None => match preset(name) {
+                Some(list) => list.iter().map(|ext| ext.to_string()).collect(),
+                None => return Err(format!("Unknown preset '{}' (available: {})", name, PRESET_NAMES.join(", "))),
+            },
+        };
+        for extension in expansion {
+            if !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+        }
+    }
+    Ok(extensions)
+}
+
+/// Joins a root-relative path to the root as typed, leaving it bare when the root is `.`
+///
+/// # Arguments
+/// * `root` - The root path given on the command line
+/// * `relative` - The `/`-separated path relative to the root
+///
+/// # Returns
+/// * `String` - The path as reachable from the working directory
+fn display_path(root: &Path, relative: &str) -> String {
+    if root == Path::new(".") {
+        relative.to_string()
+    } else {
+        root.join(relative).display().to_string()
+    }
+}
+
+/// Compiles regex patterns given on the command line, reporting and dropping invalid ones
+///
+/// # Arguments
+/// * `patterns` - The patterns as typed by the user
+///
+/// # Returns
+/// * `Vec<Regex>` - The patterns that compiled
+fn compile_patterns(patterns: &[String]) -> Vec<Regex> {
+    patterns.iter()
+        .filter_map(|p| match Regex::new(p) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                eprintln!("Invalid regex pattern '{}': {}", p, e);
+                None
+            }
+        })
+        .collect()
 }